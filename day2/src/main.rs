@@ -1,58 +1,273 @@
-use std::str::FromStr;
+use std::{fmt, str::FromStr};
 
+use anyhow::{anyhow, bail};
+
+use navigation::{model_by_name, navigate, AimModel, DirectModel};
+use script::Script;
+use spatial::{calculate_pos_3d, parse_commands_3d};
+use trace::{Trace, TraceFormat};
+
+mod navigation;
+mod planner;
+mod script;
+mod spatial;
+mod trace;
+
+#[derive(Clone, Debug)]
 enum Command {
-    Forward(usize),
-    Up(usize),
-    Down(usize),
+    Forward(i64),
+    Back(i64),
+    Up(i64),
+    Down(i64),
+}
+
+// Splits a `<direction> <value>` command, rejecting anything after the value.
+fn split_command(s: &str) -> anyhow::Result<(&str, i64)> {
+    let mut words = s.split_whitespace();
+    let direction = words.next().ok_or_else(|| anyhow!("empty command"))?;
+    let value = words
+        .next()
+        .ok_or_else(|| anyhow!("missing value in command '{}'", s))?
+        .parse::<i64>()?;
+    if value < 0 {
+        bail!("negative value in command '{}'", s);
+    }
+    if let Some(word) = words.next() {
+        bail!("unexpected '{}' after the value in command '{}'", word, s);
+    }
+
+    Ok((direction, value))
 }
 
 impl FromStr for Command {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, anyhow::Error> {
-        let mut split = s.split(" ");
-        let direction = split.next().unwrap();
-        let value = split.next().unwrap().parse::<usize>().unwrap();
+        let (direction, value) = split_command(s)?;
 
         match direction {
             "forward" => Ok(Command::Forward(value)),
+            "back" => Ok(Command::Back(value)),
             "up" => Ok(Command::Up(value)),
             "down" => Ok(Command::Down(value)),
-            _ => unreachable!(),
+            _ => bail!("unknown direction '{}'", direction),
         }
     }
 }
 
-fn calculate_pos(commands: &Vec<Command>) -> (usize, usize) {
-    commands.iter().fold((0, 0), |(x, depth), cmd| match cmd {
-        Command::Forward(v) => (x + v, depth),
-        Command::Up(v) => (x, depth - v),
-        Command::Down(v) => (x, depth + v),
-    })
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Command::Forward(v) => write!(f, "forward {}", v),
+            Command::Back(v) => write!(f, "back {}", v),
+            Command::Up(v) => write!(f, "up {}", v),
+            Command::Down(v) => write!(f, "down {}", v),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SurfacePolicy {
+    Clamp,
+    Error,
+    AllowNegative,
 }
 
-fn calculate_pos_with_aim(commands: &Vec<Command>) -> (usize, usize) {
-    let result = commands
-        .iter()
-        .fold((0, 0, 0), |(x, depth, aim), cmd| match cmd {
-            Command::Forward(v) => (x + v, depth + (aim * v), aim),
-            Command::Up(v) => (x, depth, aim - v),
-            Command::Down(v) => (x, depth, aim + v),
-        });
+impl FromStr for SurfacePolicy {
+    type Err = anyhow::Error;
 
-    (result.0, result.1)
+    fn from_str(s: &str) -> Result<Self, anyhow::Error> {
+        match s {
+            "clamp" => Ok(SurfacePolicy::Clamp),
+            "error" => Ok(SurfacePolicy::Error),
+            "allow-negative" => Ok(SurfacePolicy::AllowNegative),
+            _ => bail!("unknown surface policy '{}'", s),
+        }
+    }
 }
 
-fn main() {
+impl SurfacePolicy {
+    // The depth to keep once a move ends at `depth`.
+    fn apply(&self, depth: i64) -> anyhow::Result<i64> {
+        if depth >= 0 {
+            return Ok(depth);
+        }
+
+        match self {
+            SurfacePolicy::Clamp => Ok(0),
+            SurfacePolicy::Error => bail!("submarine went above the surface (depth {})", depth),
+            SurfacePolicy::AllowNegative => Ok(depth),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Submarine {
+    x: i64,
+    depth: i64,
+    aim: i64,
+}
+
+impl Submarine {
+    fn new() -> Self {
+        Submarine::default()
+    }
+
+    fn move_forward(self, value: i64) -> anyhow::Result<Submarine> {
+        let x = self
+            .x
+            .checked_add(value)
+            .ok_or_else(|| anyhow!("horizontal position overflow"))?;
+
+        Ok(Submarine { x, ..self })
+    }
+
+    fn change_depth(self, delta: i64, policy: SurfacePolicy) -> anyhow::Result<Submarine> {
+        let depth = self
+            .depth
+            .checked_add(delta)
+            .ok_or_else(|| anyhow!("depth overflow"))?;
+
+        Ok(Submarine {
+            depth: policy.apply(depth)?,
+            ..self
+        })
+    }
+
+    fn change_aim(self, delta: i64) -> anyhow::Result<Submarine> {
+        let aim = self
+            .aim
+            .checked_add(delta)
+            .ok_or_else(|| anyhow!("aim overflow"))?;
+
+        Ok(Submarine { aim, ..self })
+    }
+
+    fn product(&self) -> anyhow::Result<i64> {
+        self.x
+            .checked_mul(self.depth)
+            .ok_or_else(|| anyhow!("position product overflow"))
+    }
+}
+
+fn calculate_pos(commands: &[Command], policy: SurfacePolicy) -> anyhow::Result<Submarine> {
+    navigate(&DirectModel, commands, policy)
+}
+
+fn calculate_pos_with_aim(
+    commands: &[Command],
+    policy: SurfacePolicy,
+) -> anyhow::Result<Submarine> {
+    navigate(&AimModel, commands, policy)
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut policy = SurfacePolicy::Error;
+    let mut model = None;
+    let mut trace_format: Option<TraceFormat> = None;
+    let mut depth_limit: Option<i64> = None;
+    let mut target: Option<(i64, i64)> = None;
+    let mut three_dimensional = false;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--surface" => {
+                policy = args
+                    .next()
+                    .ok_or_else(|| anyhow!("missing value for --surface"))?
+                    .parse()?
+            }
+            "--model" => {
+                let name = args
+                    .next()
+                    .ok_or_else(|| anyhow!("missing value for --model"))?;
+                model = Some(model_by_name(&name)?);
+            }
+            "--trace" => {
+                trace_format = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("missing value for --trace"))?
+                        .parse()?,
+                );
+            }
+            "--depth-limit" => {
+                depth_limit = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("missing value for --depth-limit"))?
+                        .parse()?,
+                );
+            }
+            "--plan" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow!("missing value for --plan"))?;
+                let (x, depth) = value
+                    .split_once(',')
+                    .ok_or_else(|| anyhow!("expected 'x,depth' for --plan, got '{}'", value))?;
+                target = Some((x.trim().parse()?, depth.trim().parse()?));
+            }
+            "--3d" => three_dimensional = true,
+            _ => bail!("unknown argument '{}'", arg),
+        }
+    }
+
+    if let Some((x, depth)) = target {
+        let model = match model {
+            Some(model) => model,
+            None => model_by_name("aim")?,
+        };
+        for cmd in model.plan(x, depth, policy)? {
+            println!("{}", cmd);
+        }
+        return Ok(());
+    }
+
     let input = include_str!("../input.txt");
-    let commands: Vec<Command> = input
-        .lines()
-        .map(|v| v.parse::<Command>().unwrap())
-        .collect();
 
-    let pos = calculate_pos(&commands);
-    println!("part 1: {}", pos.0 * pos.1);
+    if three_dimensional {
+        let pos = calculate_pos_3d(&parse_commands_3d(input)?, policy)?;
+        println!("position: ({}, {}, {})", pos.x, pos.y, pos.depth);
+        println!("manhattan distance: {}", pos.manhattan_distance());
+        println!("euclidean distance: {:.3}", pos.euclidean_distance());
+        return Ok(());
+    }
+
+    let commands = Script::parse(input)?.commands()?;
+
+    if trace_format.is_some() || depth_limit.is_some() {
+        let model = match model {
+            Some(model) => model,
+            None => model_by_name("aim")?,
+        };
+        let trace = Trace::record(model.as_ref(), &commands, policy)?;
+
+        if let Some(format) = trace_format {
+            print!("{}", trace.export(format));
+        }
+        if let Some(max_depth) = trace.max_depth() {
+            eprintln!("max depth: {}", max_depth);
+        }
+        if let Some(depth_limit) = depth_limit {
+            match trace.first_step_deeper_than(depth_limit) {
+                Some(step) => eprintln!("first step deeper than {}: {}", depth_limit, step.index),
+                None => eprintln!("never deeper than {}", depth_limit),
+            }
+        }
+        return Ok(());
+    }
+
+    if let Some(model) = model {
+        let pos = navigate(model.as_ref(), &commands, policy)?;
+        println!("{}: {}", model.name(), pos.product()?);
+        return Ok(());
+    }
+
+    let pos = calculate_pos(&commands, policy)?;
+    println!("part 1: {}", pos.product()?);
+
+    let pos = calculate_pos_with_aim(&commands, policy)?;
+    println!("part 2: {}", pos.product()?);
 
-    let pos = calculate_pos_with_aim(&commands);
-    println!("part 2: {}", pos.0 * pos.1);
+    Ok(())
 }
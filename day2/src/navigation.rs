@@ -0,0 +1,115 @@
+use anyhow::{anyhow, bail};
+
+use crate::planner::{plan_direct, plan_with_aim};
+use crate::{Command, Submarine, SurfacePolicy};
+
+pub trait NavigationModel {
+    fn name(&self) -> &'static str;
+
+    fn apply(
+        &self,
+        submarine: Submarine,
+        command: &Command,
+        policy: SurfacePolicy,
+    ) -> anyhow::Result<Submarine>;
+
+    fn plan(&self, _x: i64, _depth: i64, _policy: SurfacePolicy) -> anyhow::Result<Vec<Command>> {
+        bail!("navigation model '{}' has no planner", self.name())
+    }
+}
+
+// `forward`/`back` move horizontally, `up`/`down` change the depth directly.
+pub struct DirectModel;
+
+impl NavigationModel for DirectModel {
+    fn name(&self) -> &'static str {
+        "direct"
+    }
+
+    fn apply(
+        &self,
+        submarine: Submarine,
+        command: &Command,
+        policy: SurfacePolicy,
+    ) -> anyhow::Result<Submarine> {
+        match command {
+            Command::Forward(v) => submarine.move_forward(*v),
+            Command::Back(v) => submarine.move_forward(-v),
+            Command::Up(v) => submarine.change_depth(-v, policy),
+            Command::Down(v) => submarine.change_depth(*v, policy),
+        }
+    }
+
+    fn plan(&self, x: i64, depth: i64, policy: SurfacePolicy) -> anyhow::Result<Vec<Command>> {
+        plan_direct(x, depth, policy)
+    }
+}
+
+// `up`/`down` change the aim, `forward`/`back` move along it.
+pub struct AimModel;
+
+impl AimModel {
+    fn move_along_aim(
+        submarine: Submarine,
+        value: i64,
+        policy: SurfacePolicy,
+    ) -> anyhow::Result<Submarine> {
+        let delta = submarine
+            .aim
+            .checked_mul(value)
+            .ok_or_else(|| anyhow!("depth overflow"))?;
+
+        submarine.move_forward(value)?.change_depth(delta, policy)
+    }
+}
+
+impl NavigationModel for AimModel {
+    fn name(&self) -> &'static str {
+        "aim"
+    }
+
+    fn apply(
+        &self,
+        submarine: Submarine,
+        command: &Command,
+        policy: SurfacePolicy,
+    ) -> anyhow::Result<Submarine> {
+        match command {
+            Command::Forward(v) => AimModel::move_along_aim(submarine, *v, policy),
+            Command::Back(v) => AimModel::move_along_aim(submarine, -v, policy),
+            Command::Up(v) => submarine.change_aim(-v),
+            Command::Down(v) => submarine.change_aim(*v),
+        }
+    }
+
+    fn plan(&self, x: i64, depth: i64, policy: SurfacePolicy) -> anyhow::Result<Vec<Command>> {
+        plan_with_aim(x, depth, policy)
+    }
+}
+
+pub fn models() -> Vec<Box<dyn NavigationModel>> {
+    vec![Box::new(DirectModel), Box::new(AimModel)]
+}
+
+pub fn model_by_name(name: &str) -> anyhow::Result<Box<dyn NavigationModel>> {
+    let models = models();
+    let names: Vec<&str> = models.iter().map(|model| model.name()).collect();
+    let names = names.join(", ");
+
+    match models.into_iter().find(|model| model.name() == name) {
+        Some(model) => Ok(model),
+        None => bail!("unknown navigation model '{}' (available: {})", name, names),
+    }
+}
+
+pub fn navigate(
+    model: &dyn NavigationModel,
+    commands: &[Command],
+    policy: SurfacePolicy,
+) -> anyhow::Result<Submarine> {
+    commands
+        .iter()
+        .try_fold(Submarine::new(), |submarine, cmd| {
+            model.apply(submarine, cmd, policy)
+        })
+}
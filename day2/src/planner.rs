@@ -0,0 +1,101 @@
+use anyhow::{anyhow, bail};
+
+use crate::{calculate_pos, calculate_pos_with_aim, Command, Submarine, SurfacePolicy};
+
+fn horizontal(value: i64) -> Command {
+    if value < 0 {
+        Command::Back(-value)
+    } else {
+        Command::Forward(value)
+    }
+}
+
+fn vertical(value: i64) -> Command {
+    if value < 0 {
+        Command::Up(-value)
+    } else {
+        Command::Down(value)
+    }
+}
+
+fn check_reachable(depth: i64, policy: SurfacePolicy) -> anyhow::Result<()> {
+    if depth < 0 && policy != SurfacePolicy::AllowNegative {
+        bail!("depth {} is above the surface", depth);
+    }
+    if depth == i64::MIN {
+        bail!("depth {} is out of range", depth);
+    }
+
+    Ok(())
+}
+
+fn check_plan(
+    plan: &[Command],
+    target: (i64, i64),
+    policy: SurfacePolicy,
+    calculate: fn(&[Command], SurfacePolicy) -> anyhow::Result<Submarine>,
+) -> anyhow::Result<()> {
+    let submarine = calculate(plan, policy)?;
+    if (submarine.x, submarine.depth) != target {
+        bail!(
+            "plan reached ({}, {}) instead of ({}, {})",
+            submarine.x,
+            submarine.depth,
+            target.0,
+            target.1
+        );
+    }
+
+    Ok(())
+}
+
+// Each non-zero axis needs its own command, so this is trivially minimal.
+pub fn plan_direct(x: i64, depth: i64, policy: SurfacePolicy) -> anyhow::Result<Vec<Command>> {
+    check_reachable(depth, policy)?;
+    if x == i64::MIN {
+        bail!("horizontal position {} is out of range", x);
+    }
+
+    let mut plan = Vec::new();
+    if x != 0 {
+        plan.push(horizontal(x));
+    }
+    if depth != 0 {
+        plan.push(vertical(depth));
+    }
+
+    check_plan(&plan, (x, depth), policy, calculate_pos)?;
+
+    Ok(plan)
+}
+
+// Moves made before the first aim change never change the depth, so:
+//  - a zero depth needs at most a single move,
+//  - `down a, forward v` reaches (v, a * v) when `x` divides `depth`,
+//  - otherwise `forward x - depth, down 1, forward depth` always works.
+pub fn plan_with_aim(x: i64, depth: i64, policy: SurfacePolicy) -> anyhow::Result<Vec<Command>> {
+    check_reachable(depth, policy)?;
+    if x == i64::MIN {
+        bail!("horizontal position {} is out of range", x);
+    }
+
+    let plan = if depth == 0 {
+        if x == 0 {
+            Vec::new()
+        } else {
+            vec![horizontal(x)]
+        }
+    } else if x != 0 && depth % x == 0 {
+        vec![vertical(depth / x), horizontal(x)]
+    } else {
+        let first_move = x
+            .checked_sub(depth)
+            .filter(|v| *v != i64::MIN)
+            .ok_or_else(|| anyhow!("target ({}, {}) is out of range", x, depth))?;
+        vec![horizontal(first_move), vertical(1), horizontal(depth)]
+    };
+
+    check_plan(&plan, (x, depth), policy, calculate_pos_with_aim)?;
+
+    Ok(plan)
+}
@@ -0,0 +1,165 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::Command;
+
+// Bounds the work of expanding a script, counted as statements visited plus
+// repeat iterations, so that nested repeats or macros cannot run forever.
+const MAX_EXPANSION_STEPS: usize = 1 << 22;
+
+#[derive(Debug)]
+enum Statement {
+    Command(Command),
+    Repeat(usize, Vec<(usize, Statement)>),
+    Call(String),
+}
+
+#[derive(Debug, Default)]
+pub struct Script {
+    macros: HashMap<String, Vec<(usize, Statement)>>,
+    body: Vec<(usize, Statement)>,
+}
+
+enum Block {
+    Repeat(usize, usize),
+    Macro(usize, String),
+}
+
+fn count_step(steps: &mut usize, line_number: usize) -> anyhow::Result<()> {
+    *steps += 1;
+    if *steps > MAX_EXPANSION_STEPS {
+        bail!(
+            "line {}: script takes more than {} steps to expand",
+            line_number,
+            MAX_EXPANSION_STEPS
+        );
+    }
+
+    Ok(())
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn is_keyword(s: &str) -> bool {
+    matches!(s, "forward" | "back" | "up" | "down" | "repeat" | "macro")
+}
+
+impl Script {
+    pub fn parse(input: &str) -> anyhow::Result<Script> {
+        let mut script = Script::default();
+        let mut blocks: Vec<(Block, Vec<(usize, Statement)>)> = Vec::new();
+
+        for (index, line) in input.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let statement = if line == "}" {
+                let (block, statements) = blocks
+                    .pop()
+                    .ok_or_else(|| anyhow!("line {}: unexpected '}}'", line_number))?;
+                match block {
+                    Block::Repeat(start, count) => (start, Statement::Repeat(count, statements)),
+                    Block::Macro(_, name) => {
+                        script.macros.insert(name, statements);
+                        continue;
+                    }
+                }
+            } else if let Some(header) = line.strip_suffix('{') {
+                let words: Vec<&str> = header.split_whitespace().collect();
+                let block = match words.as_slice() {
+                    ["repeat", count] => {
+                        let count = count.parse::<usize>().with_context(|| {
+                            format!("line {}: invalid repeat count '{}'", line_number, count)
+                        })?;
+                        Block::Repeat(line_number, count)
+                    }
+                    ["macro", name] => {
+                        if !blocks.is_empty() {
+                            bail!("line {}: macros must be defined at top level", line_number);
+                        }
+                        if !is_identifier(name) || is_keyword(name) {
+                            bail!("line {}: invalid macro name '{}'", line_number, name);
+                        }
+                        if script.macros.contains_key(*name) {
+                            bail!("line {}: macro '{}' already defined", line_number, name);
+                        }
+                        Block::Macro(line_number, name.to_string())
+                    }
+                    _ => bail!("line {}: invalid block '{}'", line_number, line),
+                };
+                blocks.push((block, Vec::new()));
+                continue;
+            } else if is_identifier(line) && !is_keyword(line) {
+                (line_number, Statement::Call(line.to_string()))
+            } else {
+                let command = line
+                    .parse::<Command>()
+                    .with_context(|| format!("line {}: invalid command '{}'", line_number, line))?;
+                (line_number, Statement::Command(command))
+            };
+
+            match blocks.last_mut() {
+                Some((_, statements)) => statements.push(statement),
+                None => script.body.push(statement),
+            }
+        }
+
+        if let Some((block, _)) = blocks.last() {
+            let line_number = match block {
+                Block::Repeat(line_number, _) | Block::Macro(line_number, _) => line_number,
+            };
+            bail!("line {}: unclosed block", line_number);
+        }
+
+        Ok(script)
+    }
+
+    pub fn commands(&self) -> anyhow::Result<Vec<Command>> {
+        let mut commands = Vec::new();
+        let mut steps = 0;
+        self.expand(&self.body, &mut HashSet::new(), &mut steps, &mut commands)?;
+
+        Ok(commands)
+    }
+
+    fn expand<'a>(
+        &'a self,
+        statements: &'a [(usize, Statement)],
+        calling: &mut HashSet<&'a str>,
+        steps: &mut usize,
+        commands: &mut Vec<Command>,
+    ) -> anyhow::Result<()> {
+        for (line_number, statement) in statements {
+            count_step(steps, *line_number)?;
+            match statement {
+                Statement::Command(command) => commands.push(command.clone()),
+                Statement::Repeat(count, statements) => {
+                    for _ in 0..*count {
+                        count_step(steps, *line_number)?;
+                        self.expand(statements, calling, steps, commands)?;
+                    }
+                }
+                Statement::Call(name) => {
+                    let statements = self.macros.get(name).ok_or_else(|| {
+                        anyhow!("line {}: undefined macro '{}'", line_number, name)
+                    })?;
+                    if !calling.insert(name) {
+                        bail!("line {}: recursive call to macro '{}'", line_number, name);
+                    }
+                    self.expand(statements, calling, steps, commands)?;
+                    calling.remove(name.as_str());
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+
+use crate::{split_command, SurfacePolicy};
+
+#[derive(Clone, Debug)]
+pub enum Command3d {
+    Forward(i64),
+    Back(i64),
+    Up(i64),
+    Down(i64),
+    Left(i64),
+    Right(i64),
+}
+
+impl FromStr for Command3d {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, anyhow::Error> {
+        let (direction, value) = split_command(s)?;
+
+        match direction {
+            "forward" => Ok(Command3d::Forward(value)),
+            "back" => Ok(Command3d::Back(value)),
+            "up" => Ok(Command3d::Up(value)),
+            "down" => Ok(Command3d::Down(value)),
+            "left" => Ok(Command3d::Left(value)),
+            "right" => Ok(Command3d::Right(value)),
+            _ => bail!("unknown direction '{}'", direction),
+        }
+    }
+}
+
+// Pitch and yaw work like the aim of the 2D model: moving forward by `v`
+// adds `v` to x, `yaw * v` to y and `pitch * v` to the depth.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Submarine3d {
+    pub x: i64,
+    pub y: i64,
+    pub depth: i64,
+    pub pitch: i64,
+    pub yaw: i64,
+}
+
+impl Submarine3d {
+    fn move_along_heading(self, value: i64, policy: SurfacePolicy) -> anyhow::Result<Submarine3d> {
+        let overflow = || anyhow!("position overflow");
+
+        let x = self.x.checked_add(value).ok_or_else(overflow)?;
+        let y = self
+            .yaw
+            .checked_mul(value)
+            .and_then(|v| self.y.checked_add(v))
+            .ok_or_else(overflow)?;
+        let depth = self
+            .pitch
+            .checked_mul(value)
+            .and_then(|v| self.depth.checked_add(v))
+            .ok_or_else(overflow)?;
+
+        Ok(Submarine3d {
+            x,
+            y,
+            depth: policy.apply(depth)?,
+            ..self
+        })
+    }
+
+    fn apply(self, command: &Command3d, policy: SurfacePolicy) -> anyhow::Result<Submarine3d> {
+        let overflow = || anyhow!("heading overflow");
+
+        match command {
+            Command3d::Forward(v) => self.move_along_heading(*v, policy),
+            Command3d::Back(v) => self.move_along_heading(-v, policy),
+            Command3d::Up(v) => Ok(Submarine3d {
+                pitch: self.pitch.checked_sub(*v).ok_or_else(overflow)?,
+                ..self
+            }),
+            Command3d::Down(v) => Ok(Submarine3d {
+                pitch: self.pitch.checked_add(*v).ok_or_else(overflow)?,
+                ..self
+            }),
+            Command3d::Left(v) => Ok(Submarine3d {
+                yaw: self.yaw.checked_sub(*v).ok_or_else(overflow)?,
+                ..self
+            }),
+            Command3d::Right(v) => Ok(Submarine3d {
+                yaw: self.yaw.checked_add(*v).ok_or_else(overflow)?,
+                ..self
+            }),
+        }
+    }
+
+    pub fn manhattan_distance(&self) -> u128 {
+        [self.x, self.y, self.depth]
+            .iter()
+            .map(|v| v.unsigned_abs() as u128)
+            .sum()
+    }
+
+    pub fn euclidean_distance(&self) -> f64 {
+        [self.x, self.y, self.depth]
+            .iter()
+            .map(|v| (*v as f64).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+}
+
+pub fn parse_commands_3d(input: &str) -> anyhow::Result<Vec<Command3d>> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.split('#').next().unwrap().trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(line_number, line)| {
+            line.parse::<Command3d>()
+                .with_context(|| format!("line {}: invalid command '{}'", line_number, line))
+        })
+        .collect()
+}
+
+pub fn calculate_pos_3d(
+    commands: &[Command3d],
+    policy: SurfacePolicy,
+) -> anyhow::Result<Submarine3d> {
+    commands
+        .iter()
+        .try_fold(Submarine3d::default(), |submarine, cmd| {
+            submarine.apply(cmd, policy)
+        })
+}
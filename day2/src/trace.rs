@@ -0,0 +1,96 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+
+use crate::navigation::NavigationModel;
+use crate::{Command, Submarine, SurfacePolicy};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TraceFormat {
+    Csv,
+    Json,
+}
+
+impl FromStr for TraceFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, anyhow::Error> {
+        match s {
+            "csv" => Ok(TraceFormat::Csv),
+            "json" => Ok(TraceFormat::Json),
+            _ => bail!("unknown trace format '{}'", s),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TraceStep {
+    pub index: usize,
+    pub submarine: Submarine,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Trace(Vec<TraceStep>);
+
+impl Trace {
+    pub fn record(
+        model: &dyn NavigationModel,
+        commands: &[Command],
+        policy: SurfacePolicy,
+    ) -> anyhow::Result<Trace> {
+        let mut steps = Vec::with_capacity(commands.len());
+        let mut submarine = Submarine::new();
+        for (index, cmd) in commands.iter().enumerate() {
+            submarine = model.apply(submarine, cmd, policy)?;
+            steps.push(TraceStep { index, submarine });
+        }
+
+        Ok(Trace(steps))
+    }
+
+    pub fn max_depth(&self) -> Option<i64> {
+        self.0.iter().map(|step| step.submarine.depth).max()
+    }
+
+    pub fn first_step_deeper_than(&self, depth: i64) -> Option<&TraceStep> {
+        self.0.iter().find(|step| step.submarine.depth > depth)
+    }
+
+    pub fn export(&self, format: TraceFormat) -> String {
+        match format {
+            TraceFormat::Csv => self.to_csv(),
+            TraceFormat::Json => self.to_json(),
+        }
+    }
+
+    fn to_csv(&self) -> String {
+        let mut output = String::from("index,x,depth,aim\n");
+        for step in &self.0 {
+            output.push_str(&format!(
+                "{},{},{},{}\n",
+                step.index, step.submarine.x, step.submarine.depth, step.submarine.aim
+            ));
+        }
+
+        output
+    }
+
+    fn to_json(&self) -> String {
+        let steps: Vec<String> = self
+            .0
+            .iter()
+            .map(|step| {
+                format!(
+                    "  {{\"index\": {}, \"x\": {}, \"depth\": {}, \"aim\": {}}}",
+                    step.index, step.submarine.x, step.submarine.depth, step.submarine.aim
+                )
+            })
+            .collect();
+
+        if steps.is_empty() {
+            String::from("[]\n")
+        } else {
+            format!("[\n{}\n]\n", steps.join(",\n"))
+        }
+    }
+}
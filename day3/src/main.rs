@@ -1,124 +1,518 @@
-use core::num;
-use std::{convert::Infallible, str::FromStr};
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail, Context};
+
+use wide::U256;
+
+mod wide;
+
+const MAX_BIT_SIZE: usize = u128::BITS as usize;
 
 #[derive(Clone)]
-struct BinaryNumber(u32);
+struct BinaryNumber(u128);
 
-impl FromStr for BinaryNumber {
-    type Err = Infallible;
+impl BinaryNumber {
+    fn from_u128(num: u128) -> BinaryNumber {
+        BinaryNumber(num)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Encoding {
+    Binary,
+    Octal,
+    Hexadecimal,
+}
+
+impl Encoding {
+    fn name(&self) -> &'static str {
+        match self {
+            Encoding::Binary => "binary",
+            Encoding::Octal => "octal",
+            Encoding::Hexadecimal => "hexadecimal",
+        }
+    }
+
+    fn radix(&self) -> u32 {
+        match self {
+            Encoding::Binary => 2,
+            Encoding::Octal => 8,
+            Encoding::Hexadecimal => 16,
+        }
+    }
+
+    fn bits_per_digit(&self) -> usize {
+        self.radix().trailing_zeros() as usize
+    }
+}
+
+struct EncodedNumber {
+    encoding: Encoding,
+    bit_size: usize,
+    number: BinaryNumber,
+}
+
+// Lines without a prefix are binary, `0b`, `0o` and `0x` select the encoding
+// explicitly. `bit_size` is the number of bits the digits can hold, which is
+// only the report width for binary numbers.
+impl FromStr for EncodedNumber {
+    type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut num: u32 = 0b0;
-        for (index, char) in s.chars().rev().enumerate() {
-            let index_val: u32 = char.to_digit(10).unwrap();
-            num = num | (index_val << index);
+        let (encoding, digits) = if let Some(digits) = s.strip_prefix("0b") {
+            (Encoding::Binary, digits)
+        } else if let Some(digits) = s.strip_prefix("0o") {
+            (Encoding::Octal, digits)
+        } else if let Some(digits) = s.strip_prefix("0x") {
+            (Encoding::Hexadecimal, digits)
+        } else {
+            (Encoding::Binary, s)
+        };
+
+        if digits.is_empty() {
+            bail!("empty number");
         }
 
-        Ok(BinaryNumber(num))
+        let bit_size = digits.chars().count() * encoding.bits_per_digit();
+        if bit_size > MAX_BIT_SIZE {
+            bail!(
+                "{} bits is more than the supported {} bits",
+                bit_size,
+                MAX_BIT_SIZE
+            );
+        }
+
+        let mut num: u128 = 0b0;
+        for (column, char) in digits.chars().enumerate() {
+            let digit = char.to_digit(encoding.radix()).ok_or_else(|| {
+                anyhow!(
+                    "invalid {} digit '{}' at column {}",
+                    encoding.name(),
+                    char,
+                    s.len() - digits.len() + column + 1
+                )
+            })?;
+            num = (num << encoding.bits_per_digit()) | digit as u128;
+        }
+
+        Ok(EncodedNumber {
+            encoding,
+            bit_size,
+            number: BinaryNumber::from_u128(num),
+        })
     }
 }
 
-impl BinaryNumber {
-    fn from_u32(num: u32) -> BinaryNumber {
-        BinaryNumber(num)
+struct DiagnosticReport {
+    bit_size: usize,
+    numbers: Vec<BinaryNumber>,
+}
+
+impl DiagnosticReport {
+    // The width of a binary report is its number of digits, leading zeros
+    // included. Octal and hexadecimal digits can hold more bits than the report
+    // has, so their width is the highest bit set in any number unless
+    // `bit_size` gives it explicitly.
+    fn parse(input: &str, bit_size: Option<usize>) -> anyhow::Result<DiagnosticReport> {
+        let mut first: Option<(Encoding, usize)> = None;
+        let mut numbers = Vec::new();
+
+        for (index, line) in input.lines().enumerate() {
+            let line_number = index + 1;
+            let encoded = line
+                .trim()
+                .parse::<EncodedNumber>()
+                .with_context(|| format!("line {}", line_number))?;
+
+            match first {
+                None => first = Some((encoded.encoding, encoded.bit_size)),
+                Some((encoding, _)) if encoding != encoded.encoding => bail!(
+                    "line {}: expected a {} number, got a {} one",
+                    line_number,
+                    encoding.name(),
+                    encoded.encoding.name()
+                ),
+                Some((_, line_bit_size)) if line_bit_size != encoded.bit_size => bail!(
+                    "line {}: expected {} bits, got {}",
+                    line_number,
+                    line_bit_size,
+                    encoded.bit_size
+                ),
+                Some(_) => {}
+            }
+
+            if let Some(bit_size) = bit_size {
+                if bit_size < MAX_BIT_SIZE && encoded.number.0 >> bit_size != 0 {
+                    bail!(
+                        "line {}: {:#x} does not fit in {} bits",
+                        line_number,
+                        encoded.number.0,
+                        bit_size
+                    );
+                }
+            }
+
+            numbers.push(encoded.number);
+        }
+
+        let bit_size = match (bit_size, first) {
+            (_, None) => bail!("empty diagnostic report"),
+            (Some(bit_size), _) if bit_size == 0 || bit_size > MAX_BIT_SIZE => {
+                bail!("bit size must be between 1 and {}", MAX_BIT_SIZE)
+            }
+            (Some(bit_size), _) => bit_size,
+            (None, Some((Encoding::Binary, bit_size))) => bit_size,
+            (None, Some(_)) => numbers
+                .iter()
+                .map(|number| (u128::BITS - number.0.leading_zeros()) as usize)
+                .max()
+                .unwrap_or(0)
+                .max(1),
+        };
+
+        Ok(DiagnosticReport { bit_size, numbers })
     }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct BitCount {
+    zeros: usize,
+    ones: usize,
+}
+
+// Counts are kept bit-sliced: bit `j` of the count for column `pos` lives in
+// bit `pos` of `planes[j]`, so adding a number to every column at once is a
+// ripple-carry addition over the planes. The planes are flushed into the
+// histogram before they can overflow.
+const COUNTER_PLANES: usize = 8;
+const BLOCK_SIZE: usize = (1 << COUNTER_PLANES) - 1;
 
-    fn bit_size() -> usize {
-        12
+struct ColumnHistogram(Vec<BitCount>);
+
+impl ColumnHistogram {
+    fn count(report: &DiagnosticReport) -> ColumnHistogram {
+        let mut ones = vec![0; report.bit_size];
+
+        for block in report.numbers.chunks(BLOCK_SIZE) {
+            let mut planes = [0u128; COUNTER_PLANES];
+            for number in block {
+                let mut carry = number.0;
+                for plane in planes.iter_mut() {
+                    if carry == 0 {
+                        break;
+                    }
+                    let sum = *plane ^ carry;
+                    carry &= *plane;
+                    *plane = sum;
+                }
+            }
+
+            for (pos, count) in ones.iter_mut().enumerate() {
+                *count += planes
+                    .iter()
+                    .enumerate()
+                    .map(|(j, plane)| (((plane >> pos) & 0b1) as usize) << j)
+                    .sum::<usize>();
+            }
+        }
+
+        let total = report.numbers.len();
+        ColumnHistogram(
+            ones.into_iter()
+                .map(|ones| BitCount {
+                    zeros: total - ones,
+                    ones,
+                })
+                .collect(),
+        )
     }
 
-    fn bit_value(&self, pos: usize) -> u32 {
-        ((0b1 << pos) & self.0) >> pos
+    fn number_from_criteria(&self, criteria: &BitCriteria) -> anyhow::Result<BinaryNumber> {
+        let mut num = 0b0;
+        for (pos, count) in self.0.iter().enumerate() {
+            match criteria.select(*count) {
+                Some(bit) => num |= bit << pos,
+                None => bail!("tie at bit {} cannot be kept for a single number", pos),
+            }
+        }
+
+        Ok(BinaryNumber::from_u128(num))
     }
 }
 
-fn get_most_common_bit_for_pos(pos: usize, numbers: &Vec<BinaryNumber>) -> u32 {
-    let majority_limit: u32 = (numbers.len() as f64 / 2.0).ceil() as u32;
-    let count = numbers
-        .iter()
-        .fold(0, |accum, item| accum + item.bit_value(pos));
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Preference {
+    MostCommon,
+    LeastCommon,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TieBreak {
+    Zero,
+    One,
+    KeepAll,
+}
 
-    if count < majority_limit {
-        0
-    } else {
-        1
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ScanOrder {
+    MsbFirst,
+    LsbFirst,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct BitCriteria {
+    preference: Preference,
+    tie_break: TieBreak,
+    scan_order: ScanOrder,
+}
+
+impl BitCriteria {
+    const MOST_COMMON: BitCriteria = BitCriteria {
+        preference: Preference::MostCommon,
+        tie_break: TieBreak::One,
+        scan_order: ScanOrder::MsbFirst,
+    };
+
+    const LEAST_COMMON: BitCriteria = BitCriteria {
+        preference: Preference::LeastCommon,
+        tie_break: TieBreak::Zero,
+        scan_order: ScanOrder::MsbFirst,
+    };
+
+    // `None` means both bits are kept.
+    fn select(&self, count: BitCount) -> Option<u128> {
+        if count.ones == count.zeros {
+            return match self.tie_break {
+                TieBreak::Zero => Some(0),
+                TieBreak::One => Some(1),
+                TieBreak::KeepAll => None,
+            };
+        }
+
+        let most_common = if count.ones > count.zeros { 1 } else { 0 };
+        match self.preference {
+            Preference::MostCommon => Some(most_common),
+            Preference::LeastCommon => Some(1 - most_common),
+        }
     }
 }
 
-fn get_most_common_bit_number(numbers: &Vec<BinaryNumber>) -> BinaryNumber {
-    let mut val: u32 = 0b0;
+// Comma separated, e.g. `least,tie=keep,lsb`. The tie rule defaults to the
+// puzzle one (1 for the most common bit, 0 for the least common) and the scan
+// to MSB first.
+impl FromStr for BitCriteria {
+    type Err = anyhow::Error;
 
-    for pos in 0..BinaryNumber::bit_size() {
-        if get_most_common_bit_for_pos(pos, numbers) == 1 {
-            val = val | 0b1 << pos;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut preference = None;
+        let mut tie_break = None;
+        let mut scan_order = ScanOrder::MsbFirst;
+
+        for token in s.split(',').map(str::trim) {
+            match token {
+                "most" => preference = Some(Preference::MostCommon),
+                "least" => preference = Some(Preference::LeastCommon),
+                "tie=0" => tie_break = Some(TieBreak::Zero),
+                "tie=1" => tie_break = Some(TieBreak::One),
+                "tie=keep" => tie_break = Some(TieBreak::KeepAll),
+                "msb" => scan_order = ScanOrder::MsbFirst,
+                "lsb" => scan_order = ScanOrder::LsbFirst,
+                _ => bail!("unknown criteria token '{}'", token),
+            }
         }
+
+        let criteria = match preference {
+            Some(Preference::MostCommon) => BitCriteria::MOST_COMMON,
+            Some(Preference::LeastCommon) => BitCriteria::LEAST_COMMON,
+            None => bail!("criteria '{}' needs either 'most' or 'least'", s),
+        };
+
+        Ok(BitCriteria {
+            tie_break: tie_break.unwrap_or(criteria.tie_break),
+            scan_order,
+            ..criteria
+        })
     }
+}
 
-    BinaryNumber::from_u32(val)
+// Sorted numbers sharing a prefix form a contiguous range, and inside that range
+// the numbers with a 0 at the next position all come before the ones with a 1.
+// Each bit criteria step is then a binary search instead of a full rescan. For
+// an LSB first scan the numbers are sorted on their bit-reversed value.
+struct SortedReport {
+    bit_size: usize,
+    scan_order: ScanOrder,
+    keys: Vec<u128>,
 }
 
-fn get_number_from_bit_criteria<F>(numbers: &Vec<BinaryNumber>, criteria: &F) -> BinaryNumber
-where
-    F: Fn(u32, usize, &BinaryNumber) -> bool,
-{
-    let mut numbers = numbers.clone();
-    for pos in (0..BinaryNumber::bit_size()).rev() {
-        let most_common_bit = get_most_common_bit_for_pos(pos, &numbers);
-        let tmp_numbers: Vec<BinaryNumber> = numbers
-            .clone()
-            .into_iter()
-            .filter(move |v| criteria(most_common_bit, pos, v))
+impl SortedReport {
+    fn new(report: &DiagnosticReport, scan_order: ScanOrder) -> SortedReport {
+        let bit_size = report.bit_size;
+        let mut keys: Vec<u128> = report
+            .numbers
+            .iter()
+            .map(|v| SortedReport::key(v.0, bit_size, scan_order))
             .collect();
-        if tmp_numbers.is_empty() {
-            return numbers.last().unwrap().clone();
-        } else if tmp_numbers.len() == 1 {
-            return tmp_numbers[0].clone();
-        } else {
-            numbers = tmp_numbers;
+        keys.sort_unstable();
+
+        SortedReport {
+            bit_size,
+            scan_order,
+            keys,
         }
     }
 
-    numbers.last().unwrap().clone()
-}
+    // Reversing the bits is its own inverse, so this also maps keys back.
+    fn key(value: u128, bit_size: usize, scan_order: ScanOrder) -> u128 {
+        match scan_order {
+            ScanOrder::MsbFirst => value,
+            ScanOrder::LsbFirst => value.reverse_bits() >> (MAX_BIT_SIZE - bit_size),
+        }
+    }
 
-fn oxigen_generator_rating_criteria(
-    most_common_val: u32,
-    pos: usize,
-    number: &BinaryNumber,
-) -> bool {
-    let number_val_at_pos = (number.0 >> pos) & 0b1;
-    most_common_val == number_val_at_pos
-}
+    fn rating(&self, criteria: &BitCriteria) -> anyhow::Result<BinaryNumber> {
+        if criteria.scan_order != self.scan_order {
+            bail!("report is not sorted for a {:?} scan", criteria.scan_order);
+        }
 
-fn co2_scrubber_rating_criteria(most_common_val: u32, pos: usize, number: &BinaryNumber) -> bool {
-    let number_val_at_pos = (number.0 >> pos) & 0b1;
-    most_common_val != number_val_at_pos
+        // Ranges of the numbers still matching, each sharing the prefix scanned
+        // so far. A tie keeping both bits leaves several of them.
+        let mut ranges = vec![(0, self.keys.len())];
+        for pos in (0..self.bit_size).rev() {
+            if let [(start, end)] = ranges[..] {
+                if self.keys[start] == self.keys[end - 1] {
+                    break;
+                }
+            }
+
+            let splits: Vec<(usize, usize, usize)> = ranges
+                .iter()
+                .map(|(start, end)| {
+                    let split =
+                        start + self.keys[*start..*end].partition_point(|v| (v >> pos) & 0b1 == 0);
+                    (*start, split, *end)
+                })
+                .collect();
+            let count = splits
+                .iter()
+                .fold(BitCount::default(), |count, (start, split, end)| BitCount {
+                    zeros: count.zeros + split - start,
+                    ones: count.ones + end - split,
+                });
+            let bit = if count.zeros == 0 {
+                Some(1)
+            } else if count.ones == 0 {
+                Some(0)
+            } else {
+                criteria.select(count)
+            };
+
+            ranges = splits
+                .into_iter()
+                .flat_map(|(start, split, end)| match bit {
+                    Some(0) => vec![(start, split)],
+                    Some(_) => vec![(split, end)],
+                    None => vec![(start, split), (split, end)],
+                })
+                .filter(|(start, end)| start < end)
+                .collect();
+        }
+
+        let start = match ranges[..] {
+            [(start, end)] if self.keys[start] == self.keys[end - 1] => start,
+            _ => bail!("{} different numbers match the criteria", ranges.len()),
+        };
+
+        Ok(BinaryNumber::from_u128(SortedReport::key(
+            self.keys[start],
+            self.bit_size,
+            self.scan_order,
+        )))
+    }
 }
 
-fn main() {
+fn main() -> anyhow::Result<()> {
+    let mut criteria: Option<BitCriteria> = None;
+    let mut bit_size: Option<usize> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--criteria" => {
+                criteria = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("missing value for --criteria"))?
+                        .parse()?,
+                );
+            }
+            "--bits" => {
+                bit_size = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("missing value for --bits"))?
+                        .parse()?,
+                );
+            }
+            _ => bail!("unknown argument '{}'", arg),
+        }
+    }
+
     let input = include_str!("../input.txt");
 
-    let numbers: Vec<BinaryNumber> = input
-        .lines()
-        .map(|v| v.parse::<BinaryNumber>().unwrap())
-        .collect();
+    let report = DiagnosticReport::parse(input, bit_size)?;
+
+    if let Some(criteria) = criteria {
+        let rating = SortedReport::new(&report, criteria.scan_order).rating(&criteria)?;
+        println!("rating: {}", rating.0);
+        return Ok(());
+    }
+
+    let histogram = ColumnHistogram::count(&report);
+    let gamma_rate = histogram.number_from_criteria(&BitCriteria::MOST_COMMON)?;
+    let epsilon_rate = histogram.number_from_criteria(&BitCriteria::LEAST_COMMON)?;
+
+    let power_consumption = U256::product(gamma_rate.0, epsilon_rate.0);
+    println!("part 1: {}", power_consumption);
+
+    let sorted_report = SortedReport::new(&report, ScanOrder::MsbFirst);
+    let oxigen_generator_rating = sorted_report.rating(&BitCriteria::MOST_COMMON)?;
+    let co2_scrubber_rating = sorted_report.rating(&BitCriteria::LEAST_COMMON)?;
+
+    let life_support_rating = U256::product(oxigen_generator_rating.0, co2_scrubber_rating.0);
+    println!("part 2: {}", life_support_rating);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rating(input: &str, criteria: &str) -> anyhow::Result<u128> {
+        let report = DiagnosticReport::parse(input, None)?;
+        let criteria: BitCriteria = criteria.parse()?;
+        let rating = SortedReport::new(&report, criteria.scan_order).rating(&criteria)?;
+
+        Ok(rating.0)
+    }
 
-    let gamma_rate = get_most_common_bit_number(&numbers);
+    #[test]
+    fn example_ratings() {
+        let input = "00100\n11110\n10110\n10111\n10101\n01111\n\
+                     00111\n11100\n10000\n11001\n00010\n01010";
 
-    let mut mask = 0b0;
-    for pos in 0..input.lines().next().unwrap().len() {
-        mask = mask | (0b1 << pos);
+        assert_eq!(rating(input, "most").unwrap(), 23);
+        assert_eq!(rating(input, "least").unwrap(), 10);
     }
-    let epsilon_rate = (!gamma_rate.0) & mask;
 
-    println!("part 1: {}", gamma_rate.0 * epsilon_rate);
+    #[test]
+    fn keep_all_tie_splits_every_range() {
+        assert_eq!(rating("000\n011\n100\n110", "least,tie=keep").unwrap(), 3);
+    }
 
-    let oxigen_generator_rating =
-        get_number_from_bit_criteria(&numbers, &oxigen_generator_rating_criteria);
-    let co2_scrubber_rating = get_number_from_bit_criteria(&numbers, &co2_scrubber_rating_criteria);
+    #[test]
+    fn keep_all_tie_counts_every_match() {
+        let error = rating("00\n01\n10\n11", "most,tie=keep").unwrap_err();
 
-    println!(
-        "part 2: {}",
-        oxigen_generator_rating.0 * co2_scrubber_rating.0
-    );
+        assert_eq!(error.to_string(), "4 different numbers match the criteria");
+    }
 }
@@ -0,0 +1,60 @@
+use std::fmt;
+
+// Unsigned 256 bit integer as little endian 64 bit limbs, just wide enough to
+// hold the product of two 128 bit rates or ratings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct U256([u64; 4]);
+
+impl U256 {
+    pub fn product(a: u128, b: u128) -> U256 {
+        let a = [a as u64, (a >> 64) as u64];
+        let b = [b as u64, (b >> 64) as u64];
+
+        let mut limbs = [0u64; 4];
+        for (i, a) in a.iter().enumerate() {
+            let mut carry = 0u128;
+            for (j, b) in b.iter().enumerate() {
+                let product = *a as u128 * *b as u128 + limbs[i + j] as u128 + carry;
+                limbs[i + j] = product as u64;
+                carry = product >> 64;
+            }
+            limbs[i + 2] = carry as u64;
+        }
+
+        U256(limbs)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.iter().all(|limb| *limb == 0)
+    }
+
+    // Divides in place and returns the remainder.
+    fn div_rem(&mut self, divisor: u64) -> u64 {
+        let mut remainder = 0u128;
+        for limb in self.0.iter_mut().rev() {
+            let value = (remainder << 64) | *limb as u128;
+            *limb = (value / divisor as u128) as u64;
+            remainder = value % divisor as u128;
+        }
+        remainder as u64
+    }
+}
+
+impl fmt::Display for U256 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const CHUNK: u64 = 10_000_000_000_000_000_000;
+
+        let mut value = *self;
+        let mut chunks = Vec::new();
+        while !value.is_zero() {
+            chunks.push(value.div_rem(CHUNK));
+        }
+
+        let mut digits = chunks.pop().unwrap_or(0).to_string();
+        for chunk in chunks.iter().rev() {
+            digits.push_str(&format!("{:019}", chunk));
+        }
+
+        f.pad_integral(true, "", &digits)
+    }
+}
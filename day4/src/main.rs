@@ -1,4 +1,12 @@
-use std::str::FromStr;
+use std::{collections::HashMap, fmt};
+
+use anyhow::{anyhow, bail, Context};
+
+use rules::{parse_conditions, parse_masks, Rules, Scoring, WinCondition};
+use simulation::simulate;
+
+mod rules;
+mod simulation;
 
 #[derive(Clone, Debug)]
 struct Number {
@@ -18,6 +26,10 @@ impl Number {
         self.marked = true;
     }
 
+    fn unmark(&mut self) {
+        self.marked = false;
+    }
+
     fn is_marked(&self) -> bool {
         self.marked
     }
@@ -31,44 +43,37 @@ impl Number {
 struct Board(Vec<Vec<Number>>);
 
 impl Board {
-    fn parse(board_lines: &[&str]) -> Board {
-        let board: Vec<Vec<Number>> = board_lines
-            .iter()
-            .map(|v| {
-                v.split(' ')
-                    .map(|v| v.trim())
-                    .filter(|v| !v.is_empty())
-                    .map(|v| v.parse::<usize>().unwrap())
-                    .map(|v| Number::new(v))
-                    .collect()
-            })
-            .collect();
+    fn parse(board_lines: &[(usize, &str)]) -> anyhow::Result<Board> {
+        let mut board: Vec<Vec<Number>> = Vec::new();
+        for (line_number, line) in board_lines {
+            let row = line
+                .split_whitespace()
+                .map(|v| v.parse::<usize>().map(Number::new))
+                .collect::<Result<Vec<Number>, _>>()
+                .with_context(|| format!("line {}: invalid board row", line_number))?;
 
-        Board(board)
-    }
-
-    fn mark_number(&mut self, number: usize) {
-        self.0.iter_mut().flatten().for_each(|v| {
-            if v.number() == number {
-                v.mark()
+            if let Some(first_row) = board.first() {
+                if first_row.len() != row.len() {
+                    bail!(
+                        "line {}: expected {} numbers, got {}",
+                        line_number,
+                        first_row.len(),
+                        row.len()
+                    );
+                }
             }
-        })
-    }
-
-    fn is_winner(&self) -> bool {
-        let line_winning = self.0.iter().any(|line| line.iter().all(Number::is_marked));
-        if line_winning {
-            return true;
+            board.push(row);
         }
 
-        debug_assert_eq!(self.0.len(), self.0[0].len());
-        for index in 0..self.0.len() {
-            if self.0.iter().all(|v| v[index].is_marked()) {
-                return true;
-            }
-        }
+        Ok(Board(board))
+    }
 
-        return false;
+    fn rows(&self) -> usize {
+        self.0.len()
+    }
+
+    fn columns(&self) -> usize {
+        self.0[0].len()
     }
 
     fn score(&self) -> usize {
@@ -82,69 +87,469 @@ impl Board {
     }
 }
 
-fn parse(input: &str) -> (Vec<usize>, Vec<Board>) {
-    let mut input_lines = input.lines().peekable();
+// Marked numbers are shown between brackets, or in bold green with `{:#}`.
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .0
+            .iter()
+            .flatten()
+            .map(|number| number.number().to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        for line in &self.0 {
+            for (column, number) in line.iter().enumerate() {
+                if column > 0 {
+                    write!(f, " ")?;
+                }
+                match (number.is_marked(), f.alternate()) {
+                    (true, true) => write!(f, " \x1b[1;32m{:>width$}\x1b[0m ", number.number())?,
+                    (true, false) => write!(f, "[{:>width$}]", number.number())?,
+                    (false, _) => write!(f, " {:>width$} ", number.number())?,
+                }
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn parse(input: &str) -> anyhow::Result<(Vec<usize>, Vec<Board>)> {
+    let mut input_lines = input.lines().enumerate();
 
     let numbers_drawn: Vec<usize> = input_lines
         .next()
-        .unwrap()
+        .map(|(_, line)| line)
+        .unwrap_or_default()
         .split(',')
-        .map(|v| v.parse::<usize>().unwrap())
-        .collect();
+        .map(|v| v.trim().parse::<usize>())
+        .collect::<Result<_, _>>()
+        .context("line 1: invalid drawn numbers")?;
 
     let mut boards = Vec::new();
-    while input_lines.peek().is_some() {
-        input_lines.next().unwrap(); // consume empty lines
+    let mut board_lines: Vec<(usize, &str)> = Vec::new();
+    for (index, line) in input_lines {
+        if line.trim().is_empty() {
+            if !board_lines.is_empty() {
+                boards.push(Board::parse(&board_lines)?);
+                board_lines.clear();
+            }
+        } else {
+            board_lines.push((index + 1, line));
+        }
+    }
+    if !board_lines.is_empty() {
+        boards.push(Board::parse(&board_lines)?);
+    }
+
+    Ok((numbers_drawn, boards))
+}
 
-        let board_lines: Vec<&str> = input_lines.by_ref().take(5).collect();
-        boards.push(Board::parse(&board_lines));
+#[derive(Clone, Debug)]
+struct BoardState {
+    shape: usize,
+    pattern_hits: Vec<usize>,
+    unmarked_sum: usize,
+    total_sum: usize,
+    winning_pattern: Option<usize>,
+}
+
+// The win patterns of every board size, with the patterns each cell is part of.
+struct Shape {
+    size: (usize, usize),
+    patterns: Vec<Vec<(usize, usize)>>,
+    cell_patterns: Vec<Vec<usize>>,
+}
+
+impl Shape {
+    fn new(rows: usize, columns: usize, rules: &Rules) -> Shape {
+        let patterns: Vec<Vec<(usize, usize)>> = rules
+            .conditions
+            .iter()
+            .flat_map(|condition| condition.patterns(rows, columns))
+            .collect();
+
+        let mut cell_patterns = vec![Vec::new(); rows * columns];
+        for (index, pattern) in patterns.iter().enumerate() {
+            for (row, column) in pattern {
+                cell_patterns[row * columns + column].push(index);
+            }
+        }
+
+        Shape {
+            size: (rows, columns),
+            patterns,
+            cell_patterns,
+        }
     }
+}
 
-    (numbers_drawn, boards)
+// Every drawn number is looked up in `cells` and only touches the matching
+// cells, whose pattern counters tell in O(1) whether the board won.
+struct Engine {
+    boards: Vec<Board>,
+    states: Vec<BoardState>,
+    shapes: Vec<Shape>,
+    cells: HashMap<usize, Vec<(usize, usize, usize)>>,
+    scoring: Scoring,
 }
 
-fn winning_board(numbers_drawns: &Vec<usize>, mut boards: Vec<Board>) -> (usize, Board) {
-    for number in numbers_drawns {
-        boards
+impl Engine {
+    fn new(boards: Vec<Board>, rules: &Rules) -> Engine {
+        let mut cells: HashMap<usize, Vec<(usize, usize, usize)>> = HashMap::new();
+        for (index, board) in boards.iter().enumerate() {
+            for (row, line) in board.0.iter().enumerate() {
+                for (column, number) in line.iter().enumerate() {
+                    cells
+                        .entry(number.number())
+                        .or_default()
+                        .push((index, row, column));
+                }
+            }
+        }
+
+        let mut shapes: Vec<Shape> = Vec::new();
+        let mut states = Vec::with_capacity(boards.len());
+        for board in &boards {
+            let size = (board.rows(), board.columns());
+            let shape = match shapes.iter().position(|shape| shape.size == size) {
+                Some(shape) => shape,
+                None => {
+                    shapes.push(Shape::new(size.0, size.1, rules));
+                    shapes.len() - 1
+                }
+            };
+
+            let total_sum = board.score();
+            states.push(BoardState {
+                shape,
+                pattern_hits: vec![0; shapes[shape].patterns.len()],
+                unmarked_sum: total_sum,
+                total_sum,
+                winning_pattern: None,
+            });
+        }
+
+        Engine {
+            boards,
+            states,
+            shapes,
+            cells,
+            scoring: rules.scoring,
+        }
+    }
+
+    fn reset(&mut self) {
+        self.boards
             .iter_mut()
-            .for_each(|board| board.mark_number(*number));
+            .flat_map(|board| board.0.iter_mut().flatten())
+            .for_each(Number::unmark);
+
+        for state in self.states.iter_mut() {
+            state.pattern_hits.iter_mut().for_each(|hits| *hits = 0);
+            state.unmarked_sum = state.total_sum;
+            state.winning_pattern = None;
+        }
+    }
 
-        if let Some(board) = boards.iter().find(|board| board.is_winner()) {
-            return (*number, board.clone());
+    // Returns the boards winning with this number, in board order.
+    fn draw(&mut self, number: usize) -> Vec<usize> {
+        let mut winners = Vec::new();
+
+        let cells = match self.cells.get(&number) {
+            Some(cells) => cells,
+            None => return winners,
+        };
+
+        for (index, row, column) in cells {
+            let board = &mut self.boards[*index];
+            let cell = &mut board.0[*row][*column];
+            if cell.is_marked() {
+                continue;
+            }
+            cell.mark();
+
+            let state = &mut self.states[*index];
+            let shape = &self.shapes[state.shape];
+            state.unmarked_sum -= number;
+
+            for pattern in &shape.cell_patterns[row * shape.size.1 + column] {
+                state.pattern_hits[*pattern] += 1;
+                let complete = state.pattern_hits[*pattern] == shape.patterns[*pattern].len();
+                if complete && state.winning_pattern.is_none() {
+                    state.winning_pattern = Some(*pattern);
+                    winners.push(*index);
+                }
+            }
         }
+
+        winners
+    }
+
+    fn unmarked_sum(&self, board: usize) -> usize {
+        self.states[board].unmarked_sum
+    }
+
+    fn has_won(&self, board: usize) -> bool {
+        self.states[board].winning_pattern.is_some()
     }
 
-    unreachable!();
+    fn score(&self, board: usize, number: usize) -> usize {
+        let state = &self.states[board];
+        let sum = match self.scoring {
+            Scoring::Unmarked => state.unmarked_sum,
+            Scoring::Marked => state.total_sum - state.unmarked_sum,
+            Scoring::Pattern => match state.winning_pattern {
+                Some(pattern) => self.shapes[state.shape].patterns[pattern]
+                    .iter()
+                    .map(|(row, column)| self.boards[board].0[*row][*column].number())
+                    .sum(),
+                None => 0,
+            },
+        };
+
+        number * sum
+    }
 }
 
-fn looser_board(numbers_drawns: &Vec<usize>, mut boards: Vec<Board>) -> (usize, Board) {
-    let mut boards = boards;
-    for number in numbers_drawns {
-        boards
-            .iter_mut()
-            .for_each(|board| board.mark_number(*number));
+#[derive(Clone, Debug)]
+struct Win {
+    board: usize,
+    draw: usize,
+    number: usize,
+    score: usize,
+}
 
-        if boards.len() == 1 && boards[0].is_winner() {
-            return (*number, boards.last().unwrap().clone());
+#[derive(Clone, Debug)]
+struct NonWinner {
+    board: usize,
+    unmarked_sum: usize,
+}
+
+// Boards winning on the same draw share a round, rounds are in draw order.
+#[derive(Clone, Debug, Default)]
+struct Ranking {
+    rounds: Vec<Vec<Win>>,
+    never_won: Vec<NonWinner>,
+}
+
+impl Ranking {
+    fn first_round(&self) -> Option<&[Win]> {
+        self.rounds.first().map(Vec::as_slice)
+    }
+
+    fn last_round(&self) -> Option<&[Win]> {
+        self.rounds.last().map(Vec::as_slice)
+    }
+}
+
+fn rank(engine: &mut Engine, numbers_drawns: &[usize]) -> Ranking {
+    let mut ranking = Ranking::default();
+    let mut remaining = engine.boards.len();
+
+    for (draw, number) in numbers_drawns.iter().enumerate() {
+        if remaining == 0 {
+            break;
         }
 
-        boards = boards
+        let round: Vec<Win> = engine
+            .draw(*number)
             .into_iter()
-            .filter(|board| !board.is_winner())
+            .map(|index| Win {
+                board: index,
+                draw,
+                number: *number,
+                score: engine.score(index, *number),
+            })
             .collect();
+
+        if !round.is_empty() {
+            remaining -= round.len();
+            ranking.rounds.push(round);
+        }
     }
 
-    unreachable!();
+    ranking.never_won = (0..engine.boards.len())
+        .filter(|index| !engine.has_won(*index))
+        .map(|index| NonWinner {
+            board: index,
+            unmarked_sum: engine.unmarked_sum(index),
+        })
+        .collect();
+
+    ranking
+}
+
+fn replay(engine: &mut Engine, numbers_drawns: &[usize], color: bool) {
+    for (draw, number) in numbers_drawns.iter().enumerate() {
+        let winners = engine.draw(*number);
+
+        println!("draw {}: {}", draw, number);
+        for (index, board) in engine.boards.iter().enumerate() {
+            println!();
+            let status = if winners.contains(&index) {
+                " (winner)"
+            } else {
+                ""
+            };
+            println!("board {}{}", index, status);
+            if color {
+                print!("{:#}", board);
+            } else {
+                print!("{}", board);
+            }
+        }
+        println!();
+
+        if !winners.is_empty() {
+            return;
+        }
+    }
 }
 
-fn main() {
+fn main() -> anyhow::Result<()> {
+    let mut show_ranking = false;
+    let mut show_replay = false;
+    let mut color = false;
+    let mut trials: Option<usize> = None;
+    let mut seed = 0;
+    let mut rules = Rules::default();
+    let mut conditions: Option<Vec<WinCondition>> = None;
+    let mut masks = Vec::new();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--ranking" => show_ranking = true,
+            "--replay" => show_replay = true,
+            "--color" => color = true,
+            "--simulate" => {
+                trials = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("missing value for --simulate"))?
+                        .parse()?,
+                );
+            }
+            "--seed" => {
+                seed = args
+                    .next()
+                    .ok_or_else(|| anyhow!("missing value for --seed"))?
+                    .parse()?;
+            }
+            "--win" => {
+                conditions = Some(parse_conditions(
+                    &args
+                        .next()
+                        .ok_or_else(|| anyhow!("missing value for --win"))?,
+                )?);
+            }
+            "--mask" => {
+                let path = args
+                    .next()
+                    .ok_or_else(|| anyhow!("missing value for --mask"))?;
+                let content = std::fs::read_to_string(&path)
+                    .with_context(|| format!("cannot read mask file '{}'", path))?;
+                masks.extend(parse_masks(&content).with_context(|| path.clone())?);
+            }
+            "--score" => {
+                rules.scoring = args
+                    .next()
+                    .ok_or_else(|| anyhow!("missing value for --score"))?
+                    .parse()?;
+            }
+            _ => bail!("unknown argument '{}'", arg),
+        }
+    }
+
     let input = include_str!("../input.txt");
 
-    let (numbers_drawns, boards) = parse(input);
+    let (numbers_drawns, boards) = parse(input)?;
+
+    // Masks replace the default rows and columns unless `--win` is given.
+    match conditions {
+        Some(conditions) => rules.conditions = conditions,
+        None if !masks.is_empty() => rules.conditions.clear(),
+        None => {}
+    }
+    rules.conditions.extend(masks);
+    if rules.conditions.is_empty() {
+        bail!("no win condition left, give a --mask or another --win");
+    }
+    let mut engine = Engine::new(boards, &rules);
+
+    if show_replay {
+        replay(&mut engine, &numbers_drawns, color);
+        return Ok(());
+    }
 
-    let (winning_number, winning_board) = winning_board(&numbers_drawns, boards.clone());
-    println!("part 1: {}", winning_number * winning_board.score());
+    if let Some(trials) = trials {
+        if trials == 0 {
+            bail!("--simulate needs at least one trial");
+        }
+
+        let simulation = simulate(&mut engine, &numbers_drawns, trials, seed);
+        for (board, (first, last)) in simulation
+            .first
+            .iter()
+            .zip(simulation.last.iter())
+            .enumerate()
+        {
+            let (first_low, first_high) = first.confidence_interval(1.96);
+            let (last_low, last_high) = last.confidence_interval(1.96);
+            println!(
+                "board {}: first {:.4} [{:.4}, {:.4}], last {:.4} [{:.4}, {:.4}]",
+                board,
+                first.probability(),
+                first_low,
+                first_high,
+                last.probability(),
+                last_low,
+                last_high
+            );
+        }
+        return Ok(());
+    }
+
+    let ranking = rank(&mut engine, &numbers_drawns);
+
+    if show_ranking {
+        for (rank, round) in ranking.rounds.iter().enumerate() {
+            for win in round {
+                println!(
+                    "#{}: board {} wins on draw {} (number {}) with score {}",
+                    rank + 1,
+                    win.board,
+                    win.draw,
+                    win.number,
+                    win.score
+                );
+            }
+        }
+        for non_winner in &ranking.never_won {
+            println!(
+                "never: board {} with unmarked sum {}",
+                non_winner.board, non_winner.unmarked_sum
+            );
+        }
+        return Ok(());
+    }
+
+    match ranking.first_round() {
+        Some([win]) => println!("part 1: {}", win.score),
+        Some(round) => bail!("{} boards tie for the first win", round.len()),
+        None => bail!("no board wins"),
+    }
+
+    match ranking.last_round() {
+        Some([win]) if ranking.never_won.is_empty() => println!("part 2: {}", win.score),
+        Some(_) if !ranking.never_won.is_empty() => {
+            bail!("{} boards never win", ranking.never_won.len())
+        }
+        Some(round) => bail!("{} boards tie for the last win", round.len()),
+        None => bail!("no board wins"),
+    }
 
-    let (loosing_number, loosing_board) = looser_board(&numbers_drawns, boards.clone());
-    println!("part 2: {}", loosing_number * loosing_board.score());
+    Ok(())
 }
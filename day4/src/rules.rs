@@ -0,0 +1,159 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum WinCondition {
+    Rows,
+    Columns,
+    Diagonals,
+    Corners,
+    Blackout,
+    Mask(Vec<Vec<bool>>),
+}
+
+impl WinCondition {
+    // The groups of cells completing a board of this size. Diagonals only exist
+    // on square boards and masks only apply to boards of their own size.
+    pub fn patterns(&self, rows: usize, columns: usize) -> Vec<Vec<(usize, usize)>> {
+        match self {
+            WinCondition::Rows => (0..rows)
+                .map(|row| (0..columns).map(|column| (row, column)).collect())
+                .collect(),
+            WinCondition::Columns => (0..columns)
+                .map(|column| (0..rows).map(|row| (row, column)).collect())
+                .collect(),
+            WinCondition::Diagonals if rows == columns => vec![
+                (0..rows).map(|index| (index, index)).collect(),
+                (0..rows)
+                    .map(|index| (index, columns - 1 - index))
+                    .collect(),
+            ],
+            WinCondition::Diagonals => Vec::new(),
+            WinCondition::Corners => {
+                let mut corners = vec![
+                    (0, 0),
+                    (0, columns - 1),
+                    (rows - 1, 0),
+                    (rows - 1, columns - 1),
+                ];
+                corners.sort_unstable();
+                corners.dedup();
+                vec![corners]
+            }
+            WinCondition::Blackout => vec![(0..rows)
+                .flat_map(|row| (0..columns).map(move |column| (row, column)))
+                .collect()],
+            WinCondition::Mask(mask) if mask.len() == rows && mask[0].len() == columns => {
+                vec![mask
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(row, line)| {
+                        line.iter()
+                            .enumerate()
+                            .filter(|(_, set)| **set)
+                            .map(move |(column, _)| (row, column))
+                    })
+                    .collect()]
+            }
+            WinCondition::Mask(_) => Vec::new(),
+        }
+    }
+}
+
+// `none` gives no condition at all, for boards only won through masks.
+pub fn parse_conditions(input: &str) -> anyhow::Result<Vec<WinCondition>> {
+    if input.trim() == "none" {
+        return Ok(Vec::new());
+    }
+
+    input
+        .split(',')
+        .map(str::trim)
+        .map(|condition| match condition {
+            "rows" => Ok(WinCondition::Rows),
+            "columns" => Ok(WinCondition::Columns),
+            "diagonals" => Ok(WinCondition::Diagonals),
+            "corners" => Ok(WinCondition::Corners),
+            "blackout" => Ok(WinCondition::Blackout),
+            _ => bail!("unknown win condition '{}'", condition),
+        })
+        .collect()
+}
+
+// Masks are grids of '#' (part of the pattern) and '.' separated by blank lines.
+pub fn parse_masks(input: &str) -> anyhow::Result<Vec<WinCondition>> {
+    let mut masks = Vec::new();
+    let mut mask: Vec<Vec<bool>> = Vec::new();
+
+    for (index, line) in input.lines().chain(std::iter::once("")).enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            if !mask.is_empty() {
+                if !mask.iter().flatten().any(|set| *set) {
+                    bail!("line {}: mask without any '#' cell", index);
+                }
+                masks.push(WinCondition::Mask(std::mem::take(&mut mask)));
+            }
+            continue;
+        }
+
+        let row = line
+            .chars()
+            .map(|char| match char {
+                '#' => Ok(true),
+                '.' => Ok(false),
+                _ => bail!("line {}: invalid mask character '{}'", index + 1, char),
+            })
+            .collect::<anyhow::Result<Vec<bool>>>()?;
+
+        if let Some(first_row) = mask.first() {
+            if first_row.len() != row.len() {
+                bail!(
+                    "line {}: expected {} cells, got {}",
+                    index + 1,
+                    first_row.len(),
+                    row.len()
+                );
+            }
+        }
+        mask.push(row);
+    }
+
+    Ok(masks)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scoring {
+    Unmarked,
+    Marked,
+    Pattern,
+}
+
+impl FromStr for Scoring {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "unmarked" => Ok(Scoring::Unmarked),
+            "marked" => Ok(Scoring::Marked),
+            "pattern" => Ok(Scoring::Pattern),
+            _ => bail!("unknown scoring '{}'", s),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Rules {
+    pub conditions: Vec<WinCondition>,
+    pub scoring: Scoring,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Rules {
+            conditions: vec![WinCondition::Rows, WinCondition::Columns],
+            scoring: Scoring::Unmarked,
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::{rank, Engine};
+
+// SplitMix64, good enough to shuffle draws and reproducible from a seed.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in `0..bound`, rejecting the values that would bias the modulo.
+    fn below(&mut self, bound: u64) -> u64 {
+        let zone = u64::MAX - (u64::MAX % bound);
+        loop {
+            let value = self.next();
+            if value < zone {
+                return value % bound;
+            }
+        }
+    }
+
+    fn shuffle<T>(&mut self, values: &mut [T]) {
+        for index in (1..values.len()).rev() {
+            let other = self.below(index as u64 + 1) as usize;
+            values.swap(index, other);
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Estimate {
+    pub hits: usize,
+    pub trials: usize,
+}
+
+impl Estimate {
+    pub fn probability(&self) -> f64 {
+        self.hits as f64 / self.trials as f64
+    }
+
+    // Wilson score interval, which stays within [0, 1] for rare events.
+    pub fn confidence_interval(&self, z: f64) -> (f64, f64) {
+        let n = self.trials as f64;
+        let p = self.probability();
+        let denominator = 1.0 + z * z / n;
+        let center = (p + z * z / (2.0 * n)) / denominator;
+        let margin = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt() / denominator;
+
+        ((center - margin).max(0.0), (center + margin).min(1.0))
+    }
+}
+
+// Boards tying on the first (or last) winning draw all count as a hit, so the
+// probabilities of all boards can add up to more than one.
+pub struct Simulation {
+    pub first: Vec<Estimate>,
+    pub last: Vec<Estimate>,
+}
+
+pub fn simulate(engine: &mut Engine, numbers: &[usize], trials: usize, seed: u64) -> Simulation {
+    let boards = engine.boards.len();
+    let mut simulation = Simulation {
+        first: vec![Estimate::default(); boards],
+        last: vec![Estimate::default(); boards],
+    };
+
+    let mut random = Random(seed);
+    let mut numbers = numbers.to_vec();
+    for _ in 0..trials {
+        random.shuffle(&mut numbers);
+        engine.reset();
+        let ranking = rank(engine, &numbers);
+
+        if let Some(round) = ranking.first_round() {
+            round
+                .iter()
+                .for_each(|win| simulation.first[win.board].hits += 1);
+        }
+        if ranking.never_won.is_empty() {
+            if let Some(round) = ranking.last_round() {
+                round
+                    .iter()
+                    .for_each(|win| simulation.last[win.board].hits += 1);
+            }
+        }
+    }
+
+    simulation
+        .first
+        .iter_mut()
+        .chain(simulation.last.iter_mut())
+        .for_each(|estimate| estimate.trials = trials);
+
+    simulation
+}
@@ -0,0 +1,41 @@
+use crate::CounterMap;
+
+impl CounterMap {
+    // Count of every pixel scaled to 0..=255, row by row.
+    fn intensities(&self) -> anyhow::Result<(usize, usize, Vec<u8>)> {
+        let (width, height) = self.drawing_size()?;
+        let max_count = self.max_count().max(1) as u64;
+
+        let mut pixels = vec![0; width * height];
+        for (point, count) in self.points() {
+            let index = (point.y - self.min.y) as usize * width + (point.x - self.min.x) as usize;
+            pixels[index] = (count as u64 * 255 / max_count) as u8;
+        }
+
+        Ok((width, height, pixels))
+    }
+
+    // Binary greyscale PGM, white where the most lines overlap.
+    pub fn to_pgm(&self) -> anyhow::Result<Vec<u8>> {
+        let (width, height, pixels) = self.intensities()?;
+
+        let mut image = format!("P5\n{} {}\n255\n", width, height).into_bytes();
+        image.extend(pixels);
+
+        Ok(image)
+    }
+
+    // Binary PPM going from black through red to yellow as overlaps increase.
+    pub fn to_ppm(&self) -> anyhow::Result<Vec<u8>> {
+        let (width, height, pixels) = self.intensities()?;
+
+        let mut image = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+        for intensity in pixels {
+            let red = intensity.saturating_mul(2);
+            let green = intensity.saturating_sub(128).saturating_mul(2);
+            image.extend([red, green, 0]);
+        }
+
+        Ok(image)
+    }
+}
@@ -1,167 +1,597 @@
-use std::cmp::{max, min};
-use std::{cmp::Ordering, str::FromStr};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    str::FromStr,
+};
 
-#[derive(Eq, PartialEq, Ord, Clone, Debug)]
+use anyhow::{anyhow, bail, Context};
+
+mod heatmap;
+mod sweep;
+
+// Keeps every difference between two coordinates within an i64.
+const COORDINATE_LIMIT: i64 = 1 << 61;
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Hash)]
 struct Point {
-    x: usize,
-    y: usize,
+    x: i64,
+    y: i64,
 }
 
 impl Point {
-    fn new(x: usize, y: usize) -> Point {
+    fn new(x: i64, y: i64) -> Point {
         Point { x, y }
     }
-    fn zero() -> Point {
-        Point { x: 0, y: 0 }
-    }
-
-    fn distance(&self, point: &Point) -> f32 {
-        (((self.x.pow(2) - point.x.pow(2)) + (self.y.pow(2) - point.y.pow(2))) as f32).sqrt()
-    }
-}
-
-impl PartialOrd for Point {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        let zero = Point::zero();
-        self.distance(&zero).partial_cmp(&other.distance(&zero))
-    }
 }
 
 impl FromStr for Point {
-    type Err = std::convert::Infallible;
+    type Err = anyhow::Error;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let part: Vec<usize> = input
-            .split(",")
-            .map(str::trim)
-            .map(|v| v.parse::<usize>().unwrap())
-            .collect();
-
-        Ok(Point {
-            x: part[0],
-            y: part[1],
-        })
+        let (x, y) = input
+            .split_once(',')
+            .ok_or_else(|| anyhow!("expected 'x,y', got '{}'", input))?;
+
+        let point = Point {
+            x: x.trim().parse()?,
+            y: y.trim().parse()?,
+        };
+        if point.x.unsigned_abs() > COORDINATE_LIMIT as u64
+            || point.y.unsigned_abs() > COORDINATE_LIMIT as u64
+        {
+            bail!("coordinates of '{}' are out of range", input);
+        }
+
+        Ok(point)
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Orientation {
+    Point,
+    Horizontal,
+    Vertical,
+    Diagonal,
+    Oblique,
+}
+
+// `direction` is the smallest lattice step going from `start` to `end`, i.e.
+// the difference divided by the gcd of its components: (-1, 0, 1) on each axis
+// for horizontal, vertical and diagonal lines. The endpoints are kept in
+// input order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 struct Line {
     start: Point,
     end: Point,
+    direction: (i64, i64),
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
 }
 
 impl Line {
-    fn new(a: &Point, b: &Point) -> Line {
+    fn new(start: &Point, end: &Point) -> Line {
+        let dx = end.x - start.x;
+        let dy = end.y - start.y;
+        let steps = gcd(dx.unsigned_abs(), dy.unsigned_abs()).max(1) as i64;
+        let direction = (dx / steps, dy / steps);
+
         Line {
-            start: min(a, b).clone(),
-            end: max(a, b).clone(),
+            start: *start,
+            end: *end,
+            direction,
         }
     }
+
+    fn orientation(&self) -> Orientation {
+        let width = self.start.x.abs_diff(self.end.x);
+        let height = self.start.y.abs_diff(self.end.y);
+
+        match (width, height) {
+            (0, 0) => Orientation::Point,
+            (_, 0) => Orientation::Horizontal,
+            (0, _) => Orientation::Vertical,
+            _ if width == height => Orientation::Diagonal,
+            _ => Orientation::Oblique,
+        }
+    }
+
+    // The points of the line with integer coordinates.
+    fn points(&self) -> Vec<Point> {
+        let steps = if self.direction.0 != 0 {
+            self.start.x.abs_diff(self.end.x) / self.direction.0.unsigned_abs()
+        } else if self.direction.1 != 0 {
+            self.start.y.abs_diff(self.end.y) / self.direction.1.unsigned_abs()
+        } else {
+            0
+        } as i64;
+
+        (0..=steps)
+            .map(|v| {
+                Point::new(
+                    self.start.x + v * self.direction.0,
+                    self.start.y + v * self.direction.1,
+                )
+            })
+            .collect()
+    }
+
+    // Every cell the line goes through, one per step along its major axis.
+    fn bresenham_points(&self) -> Vec<Point> {
+        let (x1, y1) = (self.end.x, self.end.y);
+        let (mut x, mut y) = (self.start.x, self.start.y);
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let (sx, sy) = ((x1 - x).signum(), (y1 - y).signum());
+        let mut error = dx + dy;
+
+        let mut points = vec![Point::new(x, y)];
+        while x != x1 || y != y1 {
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += sx;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += sy;
+            }
+            points.push(Point::new(x, y));
+        }
+
+        points
+    }
 }
 
 impl FromStr for Line {
-    type Err = std::convert::Infallible;
+    type Err = anyhow::Error;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        let points: Vec<Point> = input
-            .split("->")
-            .map(str::trim)
-            .map(|v| v.parse::<Point>().unwrap())
-            .collect();
+        let (start, end) = input
+            .split_once("->")
+            .ok_or_else(|| anyhow!("expected 'x1,y1 -> x2,y2', got '{}'", input))?;
 
-        Ok(Line::new(&points[0], &points[1]))
+        Ok(Line::new(&start.trim().parse()?, &end.trim().parse()?))
     }
 }
 
-fn line_to_points_simple(line: &Line) -> Vec<Point> {
-    if line.start.x == line.end.x {
-        (line.start.y..=line.end.y)
-            .map(|v| Point::new(line.start.x, v))
-            .collect()
-    } else if line.start.y == line.end.y {
-        (line.start.x..=line.end.x)
-            .map(|v| Point::new(v, line.start.y))
-            .collect()
-    } else {
-        Vec::new()
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Rasterisation {
+    // Horizontal and vertical lines only.
+    Straight,
+    // Straight lines and 45 degree diagonals.
+    Diagonal,
+    // Every line, counting the points with integer coordinates only.
+    Lattice,
+    // Every line, counting every cell it crosses.
+    Bresenham,
+}
+
+impl FromStr for Rasterisation {
+    type Err = anyhow::Error;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input {
+            "straight" => Ok(Rasterisation::Straight),
+            "diagonal" => Ok(Rasterisation::Diagonal),
+            "lattice" => Ok(Rasterisation::Lattice),
+            "bresenham" => Ok(Rasterisation::Bresenham),
+            _ => bail!("unknown rasterisation '{}'", input),
+        }
     }
 }
 
-fn line_to_points_with_diagonal(line: &Line) -> Vec<Point> {
-    if line.start.x == line.end.x {
-        (line.start.y..=line.end.y)
-            .map(|v| Point::new(line.start.x, v))
-            .collect()
-    } else if line.start.y == line.end.y {
-        (line.start.x..=line.end.x)
-            .map(|v| Point::new(v, line.start.y))
-            .collect()
-    } else {
-        let length = (line.end.x - line.start.x) as isize;
-        let x_direction: isize = if line.end.x as isize - line.start.x as isize > 0 {
-            1
-        } else {
-            -1
-        };
-        let y_direction: isize = if line.end.y as isize - line.start.y as isize > 0 {
-            1
+impl Rasterisation {
+    fn includes(&self, orientation: Orientation) -> bool {
+        !matches!(
+            (self, orientation),
+            (
+                Rasterisation::Straight,
+                Orientation::Diagonal | Orientation::Oblique
+            ) | (Rasterisation::Diagonal, Orientation::Oblique)
+        )
+    }
+
+    fn line_to_points(&self, line: &Line) -> Vec<Point> {
+        if !self.includes(line.orientation()) {
+            Vec::new()
+        } else if *self == Rasterisation::Bresenham {
+            line.bresenham_points()
         } else {
-            -1
-        };
+            line.points()
+        }
+    }
+}
 
-        (0..=length)
-            .map(|v| {
-                let x = (v * x_direction) + line.start.x as isize;
-                let y = (v * y_direction) + line.start.y as isize;
+// Dense grids are fast but cost memory for the whole bounding box of the
+// lines, so far apart or huge coordinates fall back to a hash map.
+const DENSE_CELL_LIMIT: usize = 1 << 24;
 
-                Point::new(x as usize, y as usize)
-            })
-            .collect()
-    }
+enum Counts {
+    Dense { width: usize, cells: Vec<u32> },
+    Sparse(HashMap<Point, u32>),
 }
 
-struct CounterMap(Vec<Vec<i32>>);
+// Counts the lines going through every point of the `min`..=`max` box.
+struct CounterMap {
+    min: Point,
+    max: Point,
+    counts: Counts,
+}
 
 impl CounterMap {
-    fn new() -> CounterMap {
-        CounterMap(Vec::new())
+    fn for_lines(lines: &[Line]) -> CounterMap {
+        let endpoints = || lines.iter().flat_map(|line| [line.start, line.end]);
+        let min = Point::new(
+            endpoints().map(|p| p.x).min().unwrap_or(0),
+            endpoints().map(|p| p.y).min().unwrap_or(0),
+        );
+        let max = Point::new(
+            endpoints().map(|p| p.x).max().unwrap_or(0),
+            endpoints().map(|p| p.y).max().unwrap_or(0),
+        );
+
+        let width = max.x.abs_diff(min.x) as usize + 1;
+        let height = max.y.abs_diff(min.y) as usize + 1;
+        let counts = match width.checked_mul(height) {
+            Some(cells) if cells <= DENSE_CELL_LIMIT => Counts::Dense {
+                width,
+                cells: vec![0; cells],
+            },
+            _ => Counts::Sparse(HashMap::new()),
+        };
+
+        CounterMap { min, max, counts }
+    }
+
+    fn contains(&self, point: &Point) -> bool {
+        (self.min.x..=self.max.x).contains(&point.x) && (self.min.y..=self.max.y).contains(&point.y)
     }
 
     fn add_point(&mut self, point: &Point) {
-        while self.0.len() <= point.y {
-            self.0.push(Vec::new())
+        match &mut self.counts {
+            Counts::Dense { width, cells } => {
+                let index =
+                    (point.y - self.min.y) as usize * *width + (point.x - self.min.x) as usize;
+                cells[index] += 1
+            }
+            Counts::Sparse(counts) => *counts.entry(*point).or_insert(0) += 1,
+        }
+    }
+
+    fn count_at(&self, point: &Point) -> u32 {
+        if !self.contains(point) {
+            return 0;
+        }
+
+        match &self.counts {
+            Counts::Dense { width, cells } => {
+                cells[(point.y - self.min.y) as usize * *width + (point.x - self.min.x) as usize]
+            }
+            Counts::Sparse(counts) => counts.get(point).copied().unwrap_or(0),
         }
-        while self.0[point.y].len() <= point.x {
-            self.0[point.y].push(0);
+    }
+
+    fn overlaps(&self) -> usize {
+        match &self.counts {
+            Counts::Dense { cells, .. } => cells.iter().filter(|v| **v > 1).count(),
+            Counts::Sparse(counts) => counts.values().filter(|v| **v > 1).count(),
+        }
+    }
+
+    // Every point with at least one line, in no particular order.
+    fn points(&self) -> Box<dyn Iterator<Item = (Point, u32)> + '_> {
+        match &self.counts {
+            Counts::Dense { width, cells } => Box::new(
+                cells
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, count)| **count > 0)
+                    .map(move |(index, count)| {
+                        let x = self.min.x + (index % width) as i64;
+                        let y = self.min.y + (index / width) as i64;
+                        (Point::new(x, y), *count)
+                    }),
+            ),
+            Counts::Sparse(counts) => {
+                Box::new(counts.iter().map(|(point, count)| (*point, *count)))
+            }
+        }
+    }
+
+    fn max_count(&self) -> u32 {
+        self.points().map(|(_, count)| count).max().unwrap_or(0)
+    }
+
+    // The `k` points crossed by the most lines, ties broken by position.
+    fn most_dangerous(&self, k: usize) -> Vec<(Point, u32)> {
+        let mut heap = BinaryHeap::with_capacity(k + 1);
+        for (point, count) in self.points() {
+            heap.push(Reverse((count, Reverse((point.y, point.x)))));
+            if heap.len() > k {
+                heap.pop();
+            }
+        }
+
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((count, Reverse((y, x))))| (Point::new(x, y), count))
+            .collect()
+    }
+
+    // Sum of the counts of every point in the `a`..=`b` rectangle.
+    fn danger_in(&self, a: &Point, b: &Point) -> u64 {
+        let min = Point::new(a.x.min(b.x).max(self.min.x), a.y.min(b.y).max(self.min.y));
+        let max = Point::new(a.x.max(b.x).min(self.max.x), a.y.max(b.y).min(self.max.y));
+        if min.x > max.x || min.y > max.y {
+            return 0;
+        }
+
+        match &self.counts {
+            Counts::Dense { .. } => (min.y..=max.y)
+                .flat_map(|y| (min.x..=max.x).map(move |x| Point::new(x, y)))
+                .map(|point| self.count_at(&point) as u64)
+                .sum(),
+            Counts::Sparse(counts) => counts
+                .iter()
+                .filter(|(point, _)| {
+                    (min.x..=max.x).contains(&point.x) && (min.y..=max.y).contains(&point.y)
+                })
+                .map(|(_, count)| *count as u64)
+                .sum(),
+        }
+    }
+}
+
+impl CounterMap {
+    // Width and height of the bounding box, refusing boxes too large to draw.
+    fn drawing_size(&self) -> anyhow::Result<(usize, usize)> {
+        let width = self.max.x.abs_diff(self.min.x) as usize + 1;
+        let height = self.max.y.abs_diff(self.min.y) as usize + 1;
+        if width.saturating_mul(height) > DENSE_CELL_LIMIT {
+            bail!("{}x{} map is too large to draw", width, height);
+        }
+
+        Ok((width, height))
+    }
+
+    // Same layout as the puzzle description: '.' for no line, the count otherwise
+    // ('+' past 9), rows from `min.y` to `max.y`.
+    fn render(&self) -> anyhow::Result<String> {
+        let (width, height) = self.drawing_size()?;
+
+        let mut output = String::with_capacity((width + 1) * height);
+        for y in self.min.y..=self.max.y {
+            for x in self.min.x..=self.max.x {
+                match self.count_at(&Point::new(x, y)) {
+                    0 => output.push('.'),
+                    count @ 1..=9 => output.push_str(&count.to_string()),
+                    _ => output.push('+'),
+                }
+            }
+            output.push('\n');
         }
 
-        self.0[point.y][point.x] = self.0[point.y][point.x] + 1;
+        Ok(output)
     }
 }
 
-fn get_map_overlap<F>(lines: &[Line], line_to_points: F) -> usize
-where
-    F: Fn(&Line) -> Vec<Point>,
-{
-    let mut map = CounterMap::new();
+fn build_map(lines: &[Line], rasterisation: Rasterisation) -> CounterMap {
+    let mut map = CounterMap::for_lines(lines);
 
     lines
         .iter()
-        .map(line_to_points)
-        .flatten()
+        .flat_map(|line| rasterisation.line_to_points(line))
         .for_each(|v| map.add_point(&v));
 
-    map.0.iter().flatten().filter(|v| **v > 1).count()
+    map
 }
 
-fn main() {
+fn get_map_overlap(lines: &[Line], rasterisation: Rasterisation) -> usize {
+    build_map(lines, rasterisation).overlaps()
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut rasterisation: Option<Rasterisation> = None;
+    let mut analytical = false;
+    let mut check = false;
+    let mut render = false;
+    let mut heatmap: Option<String> = None;
+    let mut at: Option<Point> = None;
+    let mut top: Option<usize> = None;
+    let mut area: Option<(Point, Point)> = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--rasterisation" => {
+                rasterisation = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("missing value for --rasterisation"))?
+                        .parse()?,
+                );
+            }
+            "--analytical" => analytical = true,
+            "--check" => check = true,
+            "--render" => render = true,
+            "--heatmap" => {
+                heatmap = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("missing value for --heatmap"))?,
+                );
+            }
+            "--at" => {
+                at = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("missing value for --at"))?
+                        .parse()?,
+                );
+            }
+            "--top" => {
+                top = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("missing value for --top"))?
+                        .parse()?,
+                );
+            }
+            "--area" => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| anyhow!("missing value for --area"))?;
+                let (a, b) = value
+                    .split_once(':')
+                    .ok_or_else(|| anyhow!("expected 'x1,y1:x2,y2', got '{}'", value))?;
+                area = Some((a.parse()?, b.parse()?));
+            }
+            _ => bail!("unknown argument '{}'", arg),
+        }
+    }
+
     let input = include_str!("../input.txt");
 
-    let lines: Vec<Line> = input.lines().map(|v| v.parse::<Line>().unwrap()).collect();
+    let lines: Vec<Line> = input
+        .lines()
+        .enumerate()
+        .map(|(index, v)| {
+            v.parse::<Line>()
+                .with_context(|| format!("line {}: invalid vent line", index + 1))
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    if render || heatmap.is_some() || at.is_some() || top.is_some() || area.is_some() {
+        let map = build_map(&lines, rasterisation.unwrap_or(Rasterisation::Diagonal));
 
-    println!("part 1: {}", get_map_overlap(&lines, line_to_points_simple));
-    println!(
-        "part 2: {}",
-        get_map_overlap(&lines, line_to_points_with_diagonal)
-    );
+        if render {
+            print!("{}", map.render()?);
+        }
+        if let Some(path) = heatmap {
+            let image = if path.ends_with(".ppm") {
+                map.to_ppm()?
+            } else {
+                map.to_pgm()?
+            };
+            std::fs::write(&path, image).with_context(|| format!("cannot write '{}'", path))?;
+        }
+        if let Some(point) = at {
+            println!("{},{}: {}", point.x, point.y, map.count_at(&point));
+        }
+        if let Some(k) = top {
+            for (point, count) in map.most_dangerous(k) {
+                println!("{},{}: {}", point.x, point.y, count);
+            }
+        }
+        if let Some((a, b)) = area {
+            println!("danger: {}", map.danger_in(&a, &b));
+        }
+        return Ok(());
+    }
+
+    let count_overlaps = |rasterisation: Rasterisation| -> anyhow::Result<usize> {
+        if !analytical {
+            return Ok(get_map_overlap(&lines, rasterisation));
+        }
+
+        let count = sweep::count_overlaps(&lines, rasterisation)?;
+        if check {
+            let rasterised = get_map_overlap(&lines, rasterisation);
+            if count != rasterised {
+                bail!(
+                    "analytical count {} differs from rasterised count {}",
+                    count,
+                    rasterised
+                );
+            }
+        }
+
+        Ok(count)
+    };
+
+    if let Some(rasterisation) = rasterisation {
+        println!("overlap: {}", count_overlaps(rasterisation)?);
+        return Ok(());
+    }
+
+    println!("part 1: {}", count_overlaps(Rasterisation::Straight)?);
+    println!("part 2: {}", count_overlaps(Rasterisation::Diagonal)?);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(input: &str) -> Line {
+        input.parse().unwrap()
+    }
+
+    fn points(coordinates: &[(i64, i64)]) -> Vec<Point> {
+        coordinates
+            .iter()
+            .map(|(x, y)| Point::new(*x, *y))
+            .collect()
+    }
+
+    #[test]
+    fn straight_lines_in_both_directions() {
+        let cases = [
+            ("0,9 -> 2,9", (1, 0), &[(0, 9), (1, 9), (2, 9)]),
+            ("2,9 -> 0,9", (-1, 0), &[(2, 9), (1, 9), (0, 9)]),
+            ("7,0 -> 7,2", (0, 1), &[(7, 0), (7, 1), (7, 2)]),
+            ("7,2 -> 7,0", (0, -1), &[(7, 2), (7, 1), (7, 0)]),
+        ];
+
+        for (input, direction, expected) in cases {
+            let line = line(input);
+            assert_eq!(line.direction, direction, "{}", input);
+            assert_eq!(line.points(), points(expected), "{}", input);
+        }
+        assert_eq!(line("0,9 -> 2,9").orientation(), Orientation::Horizontal);
+        assert_eq!(line("2,9 -> 0,9").orientation(), Orientation::Horizontal);
+        assert_eq!(line("7,0 -> 7,2").orientation(), Orientation::Vertical);
+        assert_eq!(line("7,2 -> 7,0").orientation(), Orientation::Vertical);
+    }
+
+    #[test]
+    fn diagonal_lines_in_all_four_directions() {
+        let cases = [
+            ("1,1 -> 3,3", (1, 1), &[(1, 1), (2, 2), (3, 3)]),
+            ("3,3 -> 1,1", (-1, -1), &[(3, 3), (2, 2), (1, 1)]),
+            ("9,7 -> 7,9", (-1, 1), &[(9, 7), (8, 8), (7, 9)]),
+            ("7,9 -> 9,7", (1, -1), &[(7, 9), (8, 8), (9, 7)]),
+        ];
+
+        for (input, direction, expected) in cases {
+            let line = line(input);
+            assert_eq!(line.direction, direction, "{}", input);
+            assert_eq!(line.orientation(), Orientation::Diagonal, "{}", input);
+            assert_eq!(line.points(), points(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn anti_diagonal_from_the_example() {
+        let line = line("8,0 -> 0,8");
+
+        assert_eq!(line.direction, (-1, 1));
+        assert_eq!(line.orientation(), Orientation::Diagonal);
+        assert_eq!(
+            line.points(),
+            (0..=8).map(|v| Point::new(8 - v, v)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn single_point_line() {
+        let line = line("4,5 -> 4,5");
+
+        assert_eq!(line.direction, (0, 0));
+        assert_eq!(line.orientation(), Orientation::Point);
+        assert_eq!(line.points(), points(&[(4, 5)]));
+    }
 }
@@ -0,0 +1,315 @@
+use std::collections::HashMap;
+
+use anyhow::bail;
+
+use crate::{Line, Orientation, Rasterisation};
+
+type Vector = (i128, i128);
+
+fn cross(a: Vector, b: Vector) -> i128 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+// A segment with its direction normalised to point right (or down for
+// vertical lines), so that `from <= to` on the carrier.
+struct Segment {
+    start: Vector,
+    direction: Vector,
+    steps: i128,
+    carrier: usize,
+    min: Vector,
+    max: Vector,
+}
+
+// All the segments lying on the same infinite line. Lattice points on it are
+// numbered by a parameter `t` and `coverage` holds the number of segments
+// covering each `[start, end)` range of it.
+struct Carrier {
+    direction: Vector,
+    coverage: Vec<(i128, i128, u32)>,
+}
+
+impl Carrier {
+    fn parameter(direction: Vector, point: Vector) -> i128 {
+        if direction.0 != 0 {
+            point.0.div_euclid(direction.0)
+        } else {
+            point.1.div_euclid(direction.1)
+        }
+    }
+
+    fn depth_at(&self, point: Vector) -> u32 {
+        let t = Carrier::parameter(self.direction, point);
+        let index = self.coverage.partition_point(|(_, end, _)| *end <= t);
+        match self.coverage.get(index) {
+            Some((start, _, depth)) if *start <= t => *depth,
+            _ => 0,
+        }
+    }
+}
+
+fn carrier_key(direction: Vector, point: Vector) -> (Vector, i128) {
+    (direction, cross(direction, point))
+}
+
+// Number of lattice points covered by at least two lines, computed from the
+// segment geometry instead of visiting every point:
+//  - collinear overlaps come from sweeping the segments of each carrier line,
+//  - crossings of non parallel segments come from a sweep over x that only
+//    intersects segments whose bounding boxes overlap,
+//  - single point lines are looked up on the carriers going through them.
+pub fn count_overlaps(lines: &[Line], rasterisation: Rasterisation) -> anyhow::Result<usize> {
+    if rasterisation == Rasterisation::Bresenham {
+        bail!("bresenham rasterisation has no exact geometric counterpart");
+    }
+
+    let lines: Vec<&Line> = lines
+        .iter()
+        .filter(|line| rasterisation.includes(line.orientation()))
+        .collect();
+
+    let mut carriers: Vec<Carrier> = Vec::new();
+    let mut carrier_index: HashMap<(Vector, i128), usize> = HashMap::new();
+    let mut events: Vec<Vec<(i128, i32)>> = Vec::new();
+    let mut segments: Vec<Segment> = Vec::new();
+    let mut single_points: HashMap<Vector, u32> = HashMap::new();
+
+    for line in lines {
+        let mut start = (line.start.x as i128, line.start.y as i128);
+        let mut end = (line.end.x as i128, line.end.y as i128);
+        let mut direction = (line.direction.0 as i128, line.direction.1 as i128);
+
+        if line.orientation() == Orientation::Point {
+            *single_points.entry(start).or_insert(0) += 1;
+            continue;
+        }
+        if direction.0 < 0 || (direction.0 == 0 && direction.1 < 0) {
+            std::mem::swap(&mut start, &mut end);
+            direction = (-direction.0, -direction.1);
+        }
+
+        let carrier = *carrier_index
+            .entry(carrier_key(direction, start))
+            .or_insert_with(|| {
+                carriers.push(Carrier {
+                    direction,
+                    coverage: Vec::new(),
+                });
+                events.push(Vec::new());
+                carriers.len() - 1
+            });
+
+        let from = Carrier::parameter(direction, start);
+        let to = Carrier::parameter(direction, end);
+        events[carrier].push((from, 1));
+        events[carrier].push((to + 1, -1));
+
+        segments.push(Segment {
+            start,
+            direction,
+            steps: to - from,
+            carrier,
+            min: (start.0.min(end.0), start.1.min(end.1)),
+            max: (start.0.max(end.0), start.1.max(end.1)),
+        });
+    }
+
+    let mut count = 0;
+    for (carrier, mut events) in carriers.iter_mut().zip(events) {
+        events.sort_unstable();
+        let mut depth = 0;
+        for window in events.windows(2) {
+            depth += window[0].1;
+            let (start, end) = (window[0].0, window[1].0);
+            if depth > 0 && start < end {
+                carrier.coverage.push((start, end, depth as u32));
+                if depth > 1 {
+                    count += (end - start) as usize;
+                }
+            }
+        }
+    }
+
+    // Carriers going through each crossing point.
+    let mut crossings: HashMap<Vector, Vec<usize>> = HashMap::new();
+    let mut order: Vec<usize> = (0..segments.len()).collect();
+    order.sort_unstable_by_key(|index| segments[*index].min.0);
+    let mut active: Vec<usize> = Vec::new();
+    for index in order {
+        let segment = &segments[index];
+        active.retain(|other| segments[*other].max.0 >= segment.min.0);
+
+        for other in &active {
+            let other = &segments[*other];
+            if other.carrier == segment.carrier
+                || other.max.1 < segment.min.1
+                || other.min.1 > segment.max.1
+            {
+                continue;
+            }
+
+            let denominator = cross(segment.direction, other.direction);
+            if denominator == 0 {
+                continue;
+            }
+            let offset = (
+                other.start.0 - segment.start.0,
+                other.start.1 - segment.start.1,
+            );
+            let t = cross(offset, other.direction);
+            let u = cross(offset, segment.direction);
+            if t % denominator != 0 || u % denominator != 0 {
+                continue;
+            }
+            let (t, u) = (t / denominator, u / denominator);
+            if !(0..=segment.steps).contains(&t) || !(0..=other.steps).contains(&u) {
+                continue;
+            }
+
+            let point = (
+                segment.start.0 + t * segment.direction.0,
+                segment.start.1 + t * segment.direction.1,
+            );
+            let through = crossings.entry(point).or_default();
+            for carrier in [segment.carrier, other.carrier] {
+                if !through.contains(&carrier) {
+                    through.push(carrier);
+                }
+            }
+        }
+
+        active.push(index);
+    }
+
+    // A crossing point was counted once by every carrier covering it at least
+    // twice, but must be counted exactly once.
+    for (point, through) in &crossings {
+        let counted = through
+            .iter()
+            .filter(|carrier| carriers[**carrier].depth_at(*point) > 1)
+            .count();
+        count = count + 1 - counted;
+    }
+
+    let mut directions: Vec<Vector> = carriers.iter().map(|carrier| carrier.direction).collect();
+    directions.sort_unstable();
+    directions.dedup();
+    for (point, points) in single_points {
+        if crossings.contains_key(&point) {
+            continue;
+        }
+
+        let depths: Vec<u32> = directions
+            .iter()
+            .filter_map(|direction| carrier_index.get(&carrier_key(*direction, point)))
+            .map(|carrier| carriers[*carrier].depth_at(point))
+            .collect();
+        let already_counted = depths.iter().any(|depth| *depth > 1);
+        if !already_counted && points + depths.iter().sum::<u32>() > 1 {
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::get_map_overlap;
+
+    const MODES: [Rasterisation; 3] = [
+        Rasterisation::Straight,
+        Rasterisation::Diagonal,
+        Rasterisation::Lattice,
+    ];
+
+    fn parse(input: &str) -> Vec<Line> {
+        input.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    fn assert_matches_map(input: &str) {
+        let lines = parse(input);
+        for rasterisation in MODES {
+            assert_eq!(
+                count_overlaps(&lines, rasterisation).unwrap(),
+                get_map_overlap(&lines, rasterisation),
+                "{:?} on\n{}",
+                rasterisation,
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn example() {
+        assert_matches_map(
+            "0,9 -> 5,9\n8,0 -> 0,8\n9,4 -> 3,4\n2,2 -> 2,1\n7,0 -> 7,4\n\
+             6,4 -> 2,0\n0,9 -> 2,9\n3,4 -> 1,4\n0,0 -> 8,8\n5,5 -> 8,2",
+        );
+    }
+
+    #[test]
+    fn collinear_overlaps() {
+        assert_matches_map("0,0 -> 5,0\n3,0 -> 9,0\n9,0 -> 4,0");
+        assert_matches_map("2,1 -> 2,8\n2,8 -> 2,4\n2,0 -> 2,2");
+        assert_matches_map("0,0 -> 6,6\n6,6 -> 3,3\n8,8 -> 4,4");
+        assert_matches_map("0,0 -> 6,3\n8,4 -> 2,1\n4,2 -> 10,5");
+    }
+
+    #[test]
+    fn crossings_of_non_parallel_lines() {
+        assert_matches_map("0,3 -> 6,3\n3,0 -> 3,6\n0,0 -> 6,6\n6,0 -> 0,6");
+        assert_matches_map("0,0 -> 4,2\n0,2 -> 4,0\n2,0 -> 2,4");
+        // Crossing between lattice points, and a point on two overlapping carriers.
+        assert_matches_map(
+            "0,0 -> 1,1\n0,1 -> 1,0\n0,2 -> 4,2\n1,2 -> 3,2\n2,0 -> 2,4\n2,1 -> 2,3",
+        );
+    }
+
+    #[test]
+    fn point_lines() {
+        assert_matches_map("3,3 -> 3,3\n3,3 -> 3,3");
+        assert_matches_map("3,3 -> 3,3\n0,3 -> 5,3");
+        assert_matches_map("2,2 -> 2,2\n0,0 -> 4,4\n4,0 -> 0,4");
+        assert_matches_map("1,2 -> 1,2\n0,0 -> 2,4\n1,0 -> 1,2");
+    }
+
+    #[test]
+    fn negative_coordinates() {
+        assert_matches_map("-5,-5 -> 5,5\n-5,5 -> 5,-5\n-5,0 -> 5,0\n0,-5 -> 0,5");
+        assert_matches_map("-9,-3 -> -1,-3\n-4,-3 -> -12,-3\n-2,-2 -> -2,-2\n-6,-9 -> 0,3");
+    }
+
+    #[test]
+    fn random_lines() {
+        // Small xorshift generator so that the cases are reproducible.
+        let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |bound: i64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % (2 * bound as u64 + 1)) as i64 - bound
+        };
+
+        for _ in 0..200 {
+            let input = (0..8)
+                .map(|_| {
+                    let (x, y) = (next(6), next(6));
+                    let (dx, dy) = match next(2) {
+                        -2 => (next(6), 0),
+                        -1 => (0, next(6)),
+                        0 => {
+                            let d = next(6);
+                            (d, if next(1) < 0 { -d } else { d })
+                        }
+                        _ => (next(6), next(6)),
+                    };
+                    format!("{},{} -> {},{}", x, y, x + dx, y + dy)
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            assert_matches_map(&input);
+        }
+    }
+}
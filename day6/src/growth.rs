@@ -0,0 +1,113 @@
+use crate::{bigint::BigUint, lifecycle::States};
+
+// The number system populations are computed in.
+pub trait Arithmetic {
+    type Value: Clone;
+
+    fn value(&self, value: u64) -> Self::Value;
+    fn add(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+    fn mul(&self, a: &Self::Value, b: &Self::Value) -> Self::Value;
+}
+
+pub struct Exact;
+
+impl Arithmetic for Exact {
+    type Value = BigUint;
+
+    fn value(&self, value: u64) -> BigUint {
+        BigUint::from(value)
+    }
+
+    fn add(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a + b
+    }
+
+    fn mul(&self, a: &BigUint, b: &BigUint) -> BigUint {
+        a * b
+    }
+}
+
+// Arithmetic modulo a non zero modulus.
+pub struct Modular(pub u64);
+
+impl Arithmetic for Modular {
+    type Value = u64;
+
+    fn value(&self, value: u64) -> u64 {
+        value % self.0
+    }
+
+    fn add(&self, a: &u64, b: &u64) -> u64 {
+        ((*a as u128 + *b as u128) % self.0 as u128) as u64
+    }
+
+    fn mul(&self, a: &u64, b: &u64) -> u64 {
+        ((*a as u128 * *b as u128) % self.0 as u128) as u64
+    }
+}
+
+type Matrix<T> = Vec<Vec<T>>;
+
+// `transition[from][to]` is the number of fish in state `to` that a fish in
+// state `from` turns into on the next day.
+fn transition<A: Arithmetic>(arithmetic: &A, states: &States) -> Matrix<A::Value> {
+    let mut matrix = vec![vec![arithmetic.value(0); states.len()]; states.len()];
+    for (from, to) in states.transitions() {
+        matrix[from][to] = arithmetic.add(&matrix[from][to], &arithmetic.value(1));
+    }
+
+    matrix
+}
+
+fn multiply<A: Arithmetic>(
+    arithmetic: &A,
+    a: &Matrix<A::Value>,
+    b: &Matrix<A::Value>,
+) -> Matrix<A::Value> {
+    let size = b.len();
+    a.iter()
+        .map(|row| {
+            (0..size)
+                .map(|column| {
+                    row.iter()
+                        .zip(b)
+                        .map(|(value, b_row)| arithmetic.mul(value, &b_row[column]))
+                        .fold(arithmetic.value(0), |sum, value| {
+                            arithmetic.add(&sum, &value)
+                        })
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// Number of fish after `days` days, starting from `counts[state]` fish in
+// each state. The transition matrix is raised to the power `days` by squaring, so
+// this takes O(log days) matrix products.
+pub fn population<A: Arithmetic>(
+    arithmetic: &A,
+    states: &States,
+    days: u64,
+    counts: &[u64],
+) -> A::Value {
+    let mut power = transition(arithmetic, states);
+    let mut state = vec![counts
+        .iter()
+        .map(|count| arithmetic.value(*count))
+        .collect::<Vec<_>>()];
+
+    let mut days = days;
+    while days > 0 {
+        if days & 1 == 1 {
+            state = multiply(arithmetic, &state, &power);
+        }
+        days >>= 1;
+        if days > 0 {
+            power = multiply(arithmetic, &power, &power);
+        }
+    }
+
+    state[0].iter().fold(arithmetic.value(0), |sum, count| {
+        arithmetic.add(&sum, count)
+    })
+}
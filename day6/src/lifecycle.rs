@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use anyhow::bail;
+
+// How lanternfish age and reproduce. A fish spawns when its timer reaches zero
+// and the timer then restarts at `cycle - 1`, newborns start `newborn_delay`
+// days later than that. Fish younger than `maturation` days do not spawn and
+// fish die on the day they reach `mortality` days.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LifeCycle {
+    pub cycle: usize,
+    pub newborn_delay: usize,
+    pub maturation: Option<usize>,
+    pub mortality: Option<usize>,
+}
+
+impl Default for LifeCycle {
+    fn default() -> Self {
+        LifeCycle {
+            cycle: 7,
+            newborn_delay: 2,
+            maturation: None,
+            mortality: None,
+        }
+    }
+}
+
+// A fish is tracked by its age and timer. Ages only matter up to the
+// maturation (or mortality) age, so older fish share the last age class.
+type State = (usize, usize);
+
+impl LifeCycle {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.cycle == 0 {
+            bail!("cycle length must be positive");
+        }
+        match (self.maturation, self.mortality) {
+            (_, Some(0)) => bail!("mortality age must be positive"),
+            (Some(maturation), Some(mortality)) if maturation >= mortality => bail!(
+                "maturation age {} is not below the mortality age {}",
+                maturation,
+                mortality
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    fn newborn_timer(&self) -> usize {
+        self.cycle - 1 + self.newborn_delay
+    }
+
+    fn age_classes(&self) -> usize {
+        match (self.maturation, self.mortality) {
+            (_, Some(mortality)) => mortality,
+            (Some(maturation), None) => maturation + 1,
+            (None, None) => 1,
+        }
+    }
+
+    // The fish of the initial school are taken to have just matured.
+    fn initial_age(&self) -> usize {
+        self.maturation.unwrap_or(0)
+    }
+
+    // The state of a fish on the next day, if it is still alive, and whether
+    // it spawned a newborn.
+    fn next(&self, (age, timer): State) -> (Option<State>, bool) {
+        let spawns = timer == 0 && age >= self.maturation.unwrap_or(0);
+        let timer = if timer == 0 {
+            self.cycle - 1
+        } else {
+            timer - 1
+        };
+
+        let age = age + 1;
+        let state = match self.mortality {
+            Some(mortality) if age >= mortality => None,
+            _ => Some((age.min(self.age_classes() - 1), timer)),
+        };
+
+        (state, spawns)
+    }
+}
+
+// The states fish can reach under a life cycle, numbered so that populations
+// can be stored as one count per state.
+pub struct States {
+    model: LifeCycle,
+    index: HashMap<State, usize>,
+    newborn: usize,
+    // For every state, the state it turns into and whether it spawns.
+    next: Vec<(Option<usize>, bool)>,
+}
+
+impl States {
+    pub fn new(model: LifeCycle) -> Self {
+        let mut states: Vec<State> = Vec::new();
+        let mut index: HashMap<State, usize> = HashMap::new();
+        let seeds = std::iter::once((0, model.newborn_timer()))
+            .chain((0..=model.newborn_timer()).map(|timer| (model.initial_age(), timer)));
+        for state in seeds {
+            index.entry(state).or_insert_with(|| {
+                states.push(state);
+                states.len() - 1
+            });
+        }
+
+        let mut next = Vec::new();
+        let mut current = 0;
+        while current < states.len() {
+            let (state, spawns) = model.next(states[current]);
+            let state = state.map(|state| {
+                *index.entry(state).or_insert_with(|| {
+                    states.push(state);
+                    states.len() - 1
+                })
+            });
+            next.push((state, spawns));
+            current += 1;
+        }
+
+        States {
+            model,
+            newborn: index[&(0, model.newborn_timer())],
+            index,
+            next,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.next.len()
+    }
+
+    // The state of an initial fish with the given timer.
+    pub fn initial(&self, timer: usize) -> anyhow::Result<usize> {
+        if timer > self.model.newborn_timer() {
+            bail!(
+                "fish timer {} is above the newborn timer {}",
+                timer,
+                self.model.newborn_timer()
+            );
+        }
+
+        Ok(self.index[&(self.model.initial_age(), timer)])
+    }
+
+    // Every `(from, to)` move from one day to the next, including births.
+    pub fn transitions(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.next
+            .iter()
+            .enumerate()
+            .flat_map(move |(from, (to, spawns))| {
+                to.map(|to| (from, to))
+                    .into_iter()
+                    .chain(spawns.then_some((from, self.newborn)))
+            })
+    }
+}
@@ -1,28 +1,105 @@
-fn number_of_fish_after_days(days: usize, fish_ages: &[usize]) -> usize {
-    let mut fish_trackers: Vec<usize> = vec![0; 9];
-    for age in fish_ages {
-        fish_trackers[*age] += 1;
-    }
+use anyhow::{anyhow, bail, Context};
+
+use growth::{Exact, Modular};
+use lifecycle::{LifeCycle, States};
+
+mod bigint;
+mod growth;
+mod lifecycle;
 
-    let mut day_zero = 0;
-    for _ in 0..days {
-        let day_six = (day_zero + 7) % 9;
-        fish_trackers[day_six] += fish_trackers[day_zero];
-        day_zero = (day_zero + 1) % 9;
+fn main() -> anyhow::Result<()> {
+    let mut days: Option<u64> = None;
+    let mut modulo: Option<u64> = None;
+    let mut model = LifeCycle::default();
+
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--days" => {
+                days = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("missing value for --days"))?
+                        .parse()?,
+                );
+            }
+            "--modulo" => {
+                let value: u64 = args
+                    .next()
+                    .ok_or_else(|| anyhow!("missing value for --modulo"))?
+                    .parse()?;
+                if value == 0 {
+                    bail!("modulus must be positive");
+                }
+                modulo = Some(value);
+            }
+            "--cycle" => {
+                model.cycle = args
+                    .next()
+                    .ok_or_else(|| anyhow!("missing value for --cycle"))?
+                    .parse()?;
+            }
+            "--newborn-delay" => {
+                model.newborn_delay = args
+                    .next()
+                    .ok_or_else(|| anyhow!("missing value for --newborn-delay"))?
+                    .parse()?;
+            }
+            "--maturation" => {
+                model.maturation = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("missing value for --maturation"))?
+                        .parse()?,
+                );
+            }
+            "--mortality" => {
+                model.mortality = Some(
+                    args.next()
+                        .ok_or_else(|| anyhow!("missing value for --mortality"))?
+                        .parse()?,
+                );
+            }
+            _ => bail!("unknown argument '{}'", arg),
+        }
     }
 
-    fish_trackers.iter().sum()
-}
+    model.validate()?;
+    let states = States::new(model);
 
-fn main() {
     let input = include_str!("../input.txt");
 
-    let fish_ages: Vec<usize> = input
-        .split(",")
-        .map(str::parse)
-        .map(Result::unwrap)
-        .collect();
+    let mut counts: Vec<u64> = vec![0; states.len()];
+    for timer in input.trim().split(',') {
+        let timer: usize = timer
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid fish timer '{}'", timer))?;
+        counts[states.initial(timer)?] += 1;
+    }
+
+    if let Some(days) = days {
+        match modulo {
+            Some(modulus) => println!(
+                "after {} days: {}",
+                days,
+                growth::population(&Modular(modulus), &states, days, &counts)
+            ),
+            None => println!(
+                "after {} days: {}",
+                days,
+                growth::population(&Exact, &states, days, &counts)
+            ),
+        }
+        return Ok(());
+    }
+
+    println!(
+        "part 1: {}",
+        growth::population(&Exact, &states, 80, &counts)
+    );
+    println!(
+        "part 2: {}",
+        growth::population(&Exact, &states, 256, &counts)
+    );
 
-    println!("part 1: {}", number_of_fish_after_days(80, &fish_ages));
-    println!("part 2: {}", number_of_fish_after_days(256, &fish_ages));
+    Ok(())
 }